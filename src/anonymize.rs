@@ -0,0 +1,44 @@
+//! Replacing logins by pseudonyms like `reviewer-1`, to share reports without naming the reviewers.
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::BuildHasher;
+use std::path::Path;
+
+/// Numbers logins in the order of a hash salted anew for each invocation, so the numbers don't follow from the logins,
+/// but stay the same for every report of the invocation, as with `--watch`.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    salt: RandomState,
+}
+
+impl Anonymizer {
+    /// A pseudonym for each of `logins`, keyed by the login in lowercase, as logins only differing in case name the
+    /// same user.
+    pub fn pseudonyms<'a>(&self, logins: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, String> {
+        let mut users: Vec<(u64, String)> = logins
+            .into_iter()
+            .map(|login| {
+                let user = login.to_lowercase();
+                (self.salt.hash_one(&user), user)
+            })
+            .collect();
+        users.sort();
+        users.dedup();
+        users
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, user))| (user, format!("reviewer-{}", index + 1)))
+            .collect()
+    }
+}
+
+/// Write which login each of `pseudonyms` stands for to `path`, as a JSON object.
+pub fn write_map(path: &Path, pseudonyms: &BTreeMap<String, String>) -> Result<()> {
+    let by_pseudonym: BTreeMap<&str, &str> =
+        pseudonyms.iter().map(|(login, pseudonym)| (pseudonym.as_str(), login.as_str())).collect();
+    let json = serde_json::to_string_pretty(&by_pseudonym)?;
+    fs::write(path, json + "\n").with_context(|| format!("Failed to write the pseudonyms to {}", path.display()))
+}
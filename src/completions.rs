@@ -0,0 +1,84 @@
+//! Shell completion scripts, with PR numbers completed from the repository's open PRs in bash and zsh.
+
+use anyhow::{Context, Result};
+use clap::Command;
+use clap_complete::Shell;
+
+/// Wraps the generated bash completion to offer open PRs where a PR number is expected.
+const BASH_OPEN_PRS: &str = r#"
+_pr_comment_analyzer_open_prs() {
+    _pr__comment__analyzer "$@"
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "${COMP_WORDS[1]}" in
+        auth|completions|doctor|help) return ;;
+    esac
+    if [[ "$cur" == -* || ( "$prev" == -* && $COMP_CWORD -gt 1 ) ]]; then
+        return
+    fi
+
+    local repository=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -r|--repository|--remote) repository+=("${COMP_WORDS[i]}" "${COMP_WORDS[i+1]}") ;;
+        esac
+    done
+    local prs
+    prs="$(pr-comment-analyzer "${repository[@]}" --list-prs 2>/dev/null)" || return
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY+=($(compgen -W "$prs" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W "$prs" -- "$cur"))
+    fi
+}
+complete -F _pr_comment_analyzer_open_prs -o bashdefault -o default pr-comment-analyzer
+"#;
+
+/// Completes the open PRs of the repository chosen on the command line so far.
+const ZSH_OPEN_PRS: &str = r#"
+_pr_comment_analyzer_open_prs() {
+    local -a repository prs
+    local i
+    for (( i = 2; i < CURRENT; i++ )); do
+        case $words[i] in
+            -r|--repository|--remote) repository+=($words[i] $words[i+1]) ;;
+        esac
+    done
+    prs=(${(f)"$(pr-comment-analyzer $repository --list-prs 2>/dev/null)"})
+    compadd -a prs
+}
+"#;
+
+/// The action clap_complete gives the PR numbers in zsh, replaced by the open PR lookup.
+const ZSH_PR_NUMBERS_ACTION: &str = ":_default' \\";
+
+/// Write the completion script for `shell` to stdout.
+pub fn print(shell: Shell, command: &mut Command, bin_name: &str) -> Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, command, bin_name, &mut script);
+    let script = String::from_utf8(script).context("Generated completion script is not UTF-8")?;
+
+    let script = match shell {
+        Shell::Bash => script + BASH_OPEN_PRS,
+        Shell::Zsh => with_zsh_open_prs(&script),
+        _ => script,
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+/// Define the open PR lookup after the `#compdef` line and use it for the PR numbers argument.
+fn with_zsh_open_prs(script: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in script.lines() {
+        match line.strip_suffix(ZSH_PR_NUMBERS_ACTION) {
+            Some(spec) if line.starts_with("'::pr_numbers -- ") => {
+                lines.push(format!("{}:_pr_comment_analyzer_open_prs' \\", spec));
+            }
+            _ => lines.push(line.to_string()),
+        }
+        if lines.len() == 1 {
+            lines.push(ZSH_OPEN_PRS.to_string());
+        }
+    }
+    lines.join("\n") + "\n"
+}
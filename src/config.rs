@@ -0,0 +1,86 @@
+//! Defaults read from `pr-comment-analyzer/config.toml` in the user's config directory.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings from the config file; each is overridden by the corresponding command-line flag.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub token: Option<String>,
+    pub repository: Option<String>,
+    pub minutes: Option<u32>,
+    pub additional: Option<u32>,
+    pub concurrency: Option<u32>,
+    pub format: Option<String>,
+    pub api_url: Option<String>,
+    pub gitlab_url: Option<String>,
+    /// The `[profiles.NAME]` sections, each overriding the settings above when selected with `--profile NAME`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Config>,
+}
+
+impl Config {
+    /// The settings of `profile` where it has them, and the top-level ones otherwise.
+    fn with_profile(self, profile: Config) -> Config {
+        Config {
+            token: profile.token.or(self.token),
+            repository: profile.repository.or(self.repository),
+            minutes: profile.minutes.or(self.minutes),
+            additional: profile.additional.or(self.additional),
+            concurrency: profile.concurrency.or(self.concurrency),
+            format: profile.format.or(self.format),
+            api_url: profile.api_url.or(self.api_url),
+            gitlab_url: profile.gitlab_url.or(self.gitlab_url),
+            profiles: self.profiles,
+        }
+    }
+}
+
+/// The config file location: `$XDG_CONFIG_HOME/pr-comment-analyzer/config.toml`, or under `~/.config` if that isn't set.
+pub fn config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("pr-comment-analyzer").join("config.toml"))
+}
+
+/// Load the config file, or an empty `Config` if there is none, with the settings of `profile` merged over it.
+pub fn load_config(profile: Option<&str>) -> Result<Config> {
+    let mut config = read_config()?;
+    let Some(name) = profile else {
+        return Ok(config);
+    };
+    let Some(selected) = config.profiles.remove(name) else {
+        let available: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+        return Err(match available.as_slice() {
+            [] => anyhow::anyhow!("There is no profile {} in the config file, which has no profiles", name),
+            _ => anyhow::anyhow!("There is no profile {} in the config file. Available profiles: {}", name, available.join(", ")),
+        });
+    };
+    if !selected.profiles.is_empty() {
+        return Err(anyhow::anyhow!("Profile {} has profiles of its own, but profiles can't be nested", name));
+    }
+    Ok(config.with_profile(selected))
+}
+
+fn read_config() -> Result<Config> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read config file {}", path.display())),
+    };
+
+    // The TOML error names the line and column; keep it in the top-level message so it isn't lost
+    toml::from_str(&contents).map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
+}
@@ -0,0 +1,722 @@
+//! Counting the comments on a single PR, after leaving out those the options filter.
+
+use crate::forge::{Forge, PrCommentData};
+use crate::github::GitHubClient;
+use crate::{
+    get_comment_reactions, get_commit_comments, get_pr_commit_shas, graphql, AnalysisOptions, Analyzed, BodyStats, CommentFilter,
+    DateRange, MergeLatency, PrRef, PrResult, ThreadStats, UserCounts,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use indicatif::ProgressBar;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The comments of a PR by kind, or what's known of each kind.
+struct ByKind<T> {
+    /// Review comments on the diff.
+    pr_comments: T,
+    /// Reviews.
+    review_comments: T,
+    /// Conversation comments.
+    issue_comments: T,
+    /// Comments on the commits, if they are fetched.
+    commit_comments: Option<T>,
+}
+
+impl<T> ByKind<T> {
+    fn as_ref(&self) -> ByKind<&T> {
+        ByKind {
+            pr_comments: &self.pr_comments,
+            review_comments: &self.review_comments,
+            issue_comments: &self.issue_comments,
+            commit_comments: self.commit_comments.as_ref(),
+        }
+    }
+
+    /// Apply `f` to each kind, in the order of the fields.
+    fn map<U>(self, mut f: impl FnMut(T) -> U) -> ByKind<U> {
+        ByKind {
+            pr_comments: f(self.pr_comments),
+            review_comments: f(self.review_comments),
+            issue_comments: f(self.issue_comments),
+            commit_comments: self.commit_comments.map(f),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        [&self.pr_comments, &self.review_comments, &self.issue_comments].into_iter().chain(self.commit_comments.as_ref())
+    }
+}
+
+impl ByKind<u32> {
+    fn total(&self) -> u32 {
+        self.iter().sum()
+    }
+}
+
+/// Count the comments on `pr`, which is described by `pull` if it was looked up.
+pub async fn process_single_pr(
+    forge: &impl Forge,
+    pr: &PrRef,
+    pull: Option<&Value>,
+    analyzed: &Analyzed,
+    options: &AnalysisOptions,
+    bar: &ProgressBar,
+) -> Result<PrResult> {
+    let (owner, repo, pr_number) = (pr.owner.as_str(), pr.repo.as_str(), pr.number);
+    // All comments are fetched, as those outside the dates or too short are counted too, to compare
+    let PrCommentData { pr_comments, mut review_comments, issue_comments } =
+        forge.pr_comments(owner, repo, pr_number, None, None, bar).await?;
+    // The reviews of everyone count, whatever their state
+    let review_cycles = options.review_cycles.then(|| count_review_cycles(&review_comments));
+    retain_review_states(&mut review_comments, &options.review_states);
+    retain_counted_reviews(&mut review_comments, options.count_empty_reviews);
+    let commit_comments = if options.include_commit_comments {
+        let github = forge.github().context("Commit comments are only available on GitHub")?;
+        let mut comments = Vec::new();
+        for sha in get_pr_commit_shas(github, owner, repo, pr_number, bar).await? {
+            comments.extend(get_commit_comments(github, owner, repo, &sha, bar).await?);
+        }
+        Some(comments)
+    } else {
+        None
+    };
+    let fetched = ByKind { pr_comments, review_comments, issue_comments, commit_comments };
+    
+    // Responses are timed by all comments of a user, whether they are counted or not
+    let timed_comments: Vec<&Value> = fetched.iter().flatten().collect();
+    let logins = analyzed_logins(analyzed, &options.user_ids, &timed_comments);
+    let authored_by = |login: &str| -> Vec<&Value> {
+        timed_comments.iter().copied().filter(|comment| comment_author(comment, &logins) == Some(login)).collect()
+    };
+    let unfiltered = count_unfiltered(&fetched, &logins, options, bar);
+    // Without access to GraphQL, all review comments are counted rather than failing the analysis
+    let resolved_ids = match forge.github() {
+        Some(github) if options.exclude_resolved => match graphql::get_resolved_comment_ids(github, owner, repo, pr_number).await {
+            Ok(ids) => Some(ids),
+            Err(e) => {
+                eprintln!("Warning: counting all review comments of {}/{}#{}, as its resolved threads can't be told: {}", owner, repo, pr_number, e);
+                None
+            }
+        },
+        _ => None,
+    };
+    let on_paths = restrict_to_paths(&fetched, options);
+    let threads = options.thread_stats.then(|| build_comment_threads(&on_paths.pr_comments));
+    let mut excluded = BTreeMap::new();
+    let filter = options.comment_filter();
+    let mut counted = on_paths.as_ref().map(|comments| user_comments(comments, &logins, &filter, &mut excluded, bar));
+    let (pr_comments, resolved_comments) = split_resolved(counted.pr_comments, resolved_ids.as_ref());
+    let (outdated, current) = partition_outdated(&pr_comments);
+    let outdated_comments = (!outdated.is_empty() || options.exclude_outdated).then_some(outdated.len() as u32);
+    counted.pr_comments = if options.exclude_outdated { current } else { pr_comments };
+    
+    let per_user = match analyzed.is_several() {
+        true => count_per_user(&counted, &logins, analyzed, options),
+        false => BTreeMap::new(),
+    };
+    let reactions = match forge.github() {
+        Some(github) if options.reactions => Some(count_reactions(github, owner, repo, &counted, bar).await?),
+        _ => None,
+    };
+    let file_breakdown = options.file_breakdown.then(|| group_by_file(&counted.pr_comments));
+    let word_counts = options.word_counts.then(|| analyze_comment_bodies(&counted.iter().flatten().copied().collect::<Vec<_>>()));
+    let thread_stats = threads.map(|threads| measure_threads(&threads, &counted.pr_comments));
+    // PRs that weren't looked up, as with --dry-run, have no time of creation
+    let created_at = pull.and_then(|pull| pull["created_at"].as_str()).and_then(|created_at| created_at.parse().ok());
+    let several_users = analyzed.is_several();
+    let first_response = |login: &str| {
+        created_at.map(|created_at| first_response_time(created_at, &authored_by(login)).map(|time| time.num_minutes()))
+    };
+    let first_response_minutes = analyzed.login().filter(|_| options.first_response).and_then(first_response);
+    let first_responses: BTreeMap<String, Option<i64>> = match created_at {
+        Some(_) if options.first_response && several_users => {
+            logins.iter().map(|&(login, _)| (login.to_string(), first_response(login).flatten())).collect()
+        }
+        _ => BTreeMap::new(),
+    };
+    // Open PRs have no latency yet
+    let closed = pull.filter(|pull| options.merge_latency && pull["state"] == "closed");
+    let merged_at: Option<DateTime<Utc>> =
+        closed.and_then(|pull| pull["merged_at"].as_str()).and_then(|merged_at| merged_at.parse().ok());
+    let latency = |login: &str| {
+        time_after_last_comment(merged_at, &authored_by(login))
+            .map_or(MergeLatency::NoComment, |time| MergeLatency::Minutes(time.num_minutes()))
+    };
+    let merge_latency = closed.and_then(|_| match merged_at {
+        None => Some(MergeLatency::ClosedWithoutMerge),
+        Some(_) => analyzed.login().map(latency),
+    });
+    let merge_latencies: BTreeMap<String, MergeLatency> = match merged_at {
+        Some(_) if several_users => logins.iter().map(|&(login, _)| (login.to_string(), latency(login))).collect(),
+        _ => BTreeMap::new(),
+    };
+    let counts = counted.map(|comments| comments.len() as u32);
+
+    let repository = format!("{}/{}", owner, repo);
+    Ok(PrResult {
+        url: Some(forge.pr_url(&repository, pr_number)),
+        repository,
+        pr_number,
+        pr_comments: counts.pr_comments,
+        review_comments: counts.review_comments,
+        issue_comments: counts.issue_comments,
+        commit_comments: counts.commit_comments,
+        file_breakdown,
+        word_counts,
+        thread_stats,
+        first_response_minutes,
+        first_responses,
+        merge_latency,
+        merge_latencies,
+        review_cycles,
+        reactions,
+        unfiltered,
+        resolved_comments,
+        outdated_comments,
+        total: counts.total(),
+        per_user,
+        source: None,
+        base: None,
+        excluded,
+    })
+}
+
+/// Leave out the reviews in none of `states`, like `CHANGES_REQUESTED`, if any are given.
+fn retain_review_states(reviews: &mut Vec<Value>, states: &[String]) {
+    if !states.is_empty() {
+        reviews.retain(|review| review["state"].as_str().is_some_and(|state| states.iter().any(|wanted| wanted == state)));
+    }
+}
+
+/// The analyzed users, each with their id if known so comments made under an earlier login are counted too.
+/// Everyone is whoever wrote one of `comments`.
+fn analyzed_logins<'a>(analyzed: &'a Analyzed, user_ids: &HashMap<String, u64>, comments: &[&'a Value]) -> Vec<(&'a str, Option<u64>)> {
+    let id = |login: &str| user_ids.get(login).copied();
+    match analyzed {
+        Analyzed::Everyone => {
+            let authors: HashSet<&str> = comments.iter().filter_map(|comment| comment["user"]["login"].as_str()).collect();
+            authors.into_iter().map(|login| (login, None)).collect()
+        }
+        Analyzed::User(login) => vec![(login.as_str(), id(login))],
+        Analyzed::Users(users) => users.iter().map(|login| (login.as_str(), id(login))).collect(),
+    }
+}
+
+/// The comments of `logins` without the filters by date, length and path, to compare the counts with, if any of
+/// these filters are given. With `--users`, those of all users together.
+fn count_unfiltered(
+    fetched: &ByKind<Vec<Value>>,
+    logins: &[(&str, Option<u64>)],
+    options: &AnalysisOptions,
+    bar: &ProgressBar,
+) -> Option<UserCounts> {
+    let DateRange { since, until } = options.date_range;
+    if since.is_none() && until.is_none() && options.min_words == 0 && options.paths.is_none() {
+        return None;
+    }
+    let mut ignored = BTreeMap::new();
+    let unfiltered = CommentFilter { date_range: DateRange::default(), min_words: 0, ..options.comment_filter() };
+    let counts = fetched.as_ref().map(|comments| user_comments(comments, logins, &unfiltered, &mut ignored, bar).len() as u32);
+    Some(UserCounts {
+        pr_comments: counts.pr_comments,
+        review_comments: counts.review_comments,
+        issue_comments: counts.issue_comments,
+        commit_comments: counts.commit_comments,
+        total: counts.total(),
+        words: None,
+    })
+}
+
+/// The comments `--path` leaves to count: the review comments on the matching files, and the others unless
+/// `--paths-only`.
+fn restrict_to_paths<'a>(fetched: &'a ByKind<Vec<Value>>, options: &AnalysisOptions) -> ByKind<Cow<'a, [Value]>> {
+    let Some(paths) = &options.paths else {
+        return fetched.as_ref().map(|comments| Cow::from(&comments[..]));
+    };
+    let on_paths = |comment: &&Value| comment["path"].as_str().is_some_and(|path| paths.matches(path));
+    let unless_paths_only = |comments: &'a Vec<Value>| if options.paths_only { Cow::from(Vec::new()) } else { Cow::from(&comments[..]) };
+    ByKind {
+        pr_comments: Cow::from(fetched.pr_comments.iter().filter(on_paths).cloned().collect::<Vec<_>>()),
+        review_comments: unless_paths_only(&fetched.review_comments),
+        issue_comments: unless_paths_only(&fetched.issue_comments),
+        commit_comments: fetched.commit_comments.as_ref().map(unless_paths_only),
+    }
+}
+
+/// Split the review comments in resolved threads, those with `resolved_ids`, off `comments`. Returns the others and
+/// how many were resolved, or all of them without the ids.
+fn split_resolved<'a>(comments: Vec<&'a Value>, resolved_ids: Option<&HashSet<u64>>) -> (Vec<&'a Value>, Option<u32>) {
+    let Some(ids) = resolved_ids else {
+        return (comments, None);
+    };
+    let (resolved, unresolved): (Vec<&Value>, Vec<&Value>) =
+        comments.into_iter().partition(|comment| comment["id"].as_u64().is_some_and(|id| ids.contains(&id)));
+    (unresolved, Some(resolved.len() as u32))
+}
+
+/// The `counted` comments broken down by which of `logins` wrote them.
+fn count_per_user(
+    counted: &ByKind<Vec<&Value>>,
+    logins: &[(&str, Option<u64>)],
+    analyzed: &Analyzed,
+    options: &AnalysisOptions,
+) -> BTreeMap<String, UserCounts> {
+    let mut per_user: BTreeMap<String, UserCounts> = BTreeMap::new();
+    for &(login, _) in logins {
+        per_user.insert(
+            login.to_string(),
+            UserCounts {
+                commit_comments: counted.commit_comments.as_ref().map(|_| 0),
+                words: options.compare.then_some(0),
+                ..UserCounts::default()
+            },
+        );
+    }
+    // One pass over each kind of comments, counting each for its author
+    let mut tally = |comments: &[&Value], kind: fn(&mut UserCounts) -> &mut u32| {
+        for comment in comments {
+            if let Some(counts) = comment_author(comment, logins).and_then(|login| per_user.get_mut(login)) {
+                *kind(counts) += 1;
+                counts.total += 1;
+                if let Some(words) = &mut counts.words {
+                    *words += word_count(comment);
+                }
+            }
+        }
+    };
+    tally(&counted.pr_comments, |counts| &mut counts.pr_comments);
+    tally(&counted.review_comments, |counts| &mut counts.review_comments);
+    tally(&counted.issue_comments, |counts| &mut counts.issue_comments);
+    if let Some(comments) = &counted.commit_comments {
+        tally(comments, |counts| counts.commit_comments.get_or_insert(0));
+    }
+    // Only those whose comments are all excluded, outside the dates or too short have none
+    if matches!(analyzed, Analyzed::Everyone) {
+        per_user.retain(|_, counts| counts.total > 0);
+    }
+    per_user
+}
+
+/// How many reactions of each kind, like `+1`, the `counted` comments of `owner/repo` got.
+async fn count_reactions(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    counted: &ByKind<Vec<&Value>>,
+    bar: &ProgressBar,
+) -> Result<BTreeMap<String, u32>> {
+    let mut reactions = BTreeMap::new();
+    // Reviews have no reactions of their own
+    let kinds = [("pulls/comments", &counted.pr_comments), ("issues/comments", &counted.issue_comments)]
+        .into_iter()
+        .chain(counted.commit_comments.as_ref().map(|comments| ("comments", comments)));
+    for (kind, comments) in kinds {
+        for id in comments.iter().filter_map(|comment| comment["id"].as_u64()) {
+            let comment_reactions = get_comment_reactions(github, owner, repo, kind, id, bar).await?;
+            for content in comment_reactions.iter().filter_map(|reaction| reaction["content"].as_str()) {
+                *reactions.entry(content.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+    Ok(reactions)
+}
+
+/// The depth of the review `threads` the user's `pr_comments` are in, and how many of those comments are replies.
+fn measure_threads(threads: &HashMap<u64, Vec<u64>>, pr_comments: &[&Value]) -> ThreadStats {
+    let depths: Vec<u32> = threads[&0].iter().map(|&root| compute_thread_depth(threads, root)).collect();
+    ThreadStats {
+        top_level_comments: depths.len() as u32,
+        max_depth: depths.iter().copied().max().unwrap_or(0),
+        mean_depth: (!depths.is_empty()).then(|| depths.iter().sum::<u32>() as f64 / depths.len() as f64),
+        user_replies: pr_comments.iter().filter(|comment| comment["in_reply_to_id"].is_u64()).count() as u32,
+    }
+}
+
+/// The creation time of a comment, or the submission time of a review.
+pub fn comment_created_at(comment: &Value) -> Option<DateTime<Utc>> {
+    comment["created_at"]
+        .as_str()
+        .or_else(|| comment["submitted_at"].as_str())
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// The one of `logins` who wrote `comment`: by id if both are known, which survives renaming the account, and by
+/// login otherwise.
+fn comment_author<'a>(comment: &Value, logins: &[(&'a str, Option<u64>)]) -> Option<&'a str> {
+    let user = &comment["user"];
+    logins
+        .iter()
+        .find(|(login, id)| match (id, user["id"].as_u64()) {
+            (Some(id), Some(author_id)) => *id == author_id,
+            _ => user["login"].as_str() == Some(login),
+        })
+        .map(|(login, _)| *login)
+}
+
+pub fn count_user_comments(
+    comments: &[Value],
+    user: (&str, Option<u64>),
+    filter: &CommentFilter,
+    excluded: &mut BTreeMap<String, u32>,
+    bar: &ProgressBar,
+) -> u32 {
+    user_comments(comments, &[user], filter, excluded, bar).len() as u32
+}
+
+/// The comments by any of `logins`, each with its id if known, that pass `filter`, in one pass. The comments that
+/// would have been counted if not for its exclusions are added to `excluded` per author. Warnings are printed
+/// above `bar`.
+fn user_comments<'a>(
+    comments: &'a [Value],
+    logins: &[(&str, Option<u64>)],
+    filter: &CommentFilter,
+    excluded: &mut BTreeMap<String, u32>,
+    bar: &ProgressBar,
+) -> Vec<&'a Value> {
+    let CommentFilter { date_range, min_words, exclusions, anonymizer } = *filter;
+    let matching: Vec<&Value> = comments
+        .iter()
+        .filter(|comment| comment_author(comment, logins).is_some())
+        .filter(|comment| min_words == 0 || word_count(comment) >= min_words)
+        .filter(|comment| {
+            if date_range.since.is_none() && date_range.until.is_none() {
+                return true;
+            }
+            match comment_created_at(comment) {
+                Some(created_at) => date_range.contains(created_at),
+                // Leaving it out would hide it; counting it at least shows up in the total
+                None if anonymizer.is_some() => {
+                    bar.suspend(|| eprintln!("Warning: counting a comment whose creation time can't be read"));
+                    true
+                }
+                None => {
+                    let login = comment["user"]["login"].as_str().unwrap_or_default();
+                    let link = comment["html_url"].as_str().map(|url| format!(": {}", url)).unwrap_or_default();
+                    bar.suspend(|| {
+                        eprintln!("Warning: counting a comment by {} whose creation time can't be read{}", login, link)
+                    });
+                    true
+                }
+            }
+        })
+        .filter(|comment| {
+            let login = comment["user"]["login"].as_str().unwrap_or_default();
+            if exclusions.excludes(&comment["user"]) {
+                match anonymizer {
+                    Some(_) => tracing::debug!("Excluded a comment"),
+                    None => tracing::debug!("Excluded a comment by {}", login),
+                }
+            } else if let Some(pattern) = exclusions.excluding_pattern(comment) {
+                match anonymizer {
+                    Some(_) => tracing::debug!("Excluded a comment {}", pattern),
+                    None => {
+                        let link = comment["html_url"].as_str().map(|url| format!(": {}", url)).unwrap_or_default();
+                        tracing::debug!("Excluded a comment by {} {}{}", login, pattern, link);
+                    }
+                }
+            } else {
+                return true;
+            }
+            *excluded.entry(login.to_string()).or_default() += 1;
+            false
+        })
+        .collect();
+    matching
+}
+
+/// Split review `comments` into those on lines that are no longer in the diff and those on the current diff.
+/// The API has no position for the former, while a comment without the field at all, as from GitLab, is current.
+fn partition_outdated<'a>(comments: &[&'a Value]) -> (Vec<&'a Value>, Vec<&'a Value>) {
+    comments.iter().partition(|comment| comment.get("position").is_some_and(Value::is_null))
+}
+
+/// The ids of the review comments replying to each comment, with the top-level comments under 0, which is no
+/// comment's id. Replies to comments that aren't among `comments` count as top-level comments.
+fn build_comment_threads(comments: &[Value]) -> HashMap<u64, Vec<u64>> {
+    let ids: HashSet<u64> = comments.iter().filter_map(|comment| comment["id"].as_u64()).collect();
+    let mut threads: HashMap<u64, Vec<u64>> = HashMap::from([(0, Vec::new())]);
+    for comment in comments {
+        let Some(id) = comment["id"].as_u64() else { continue };
+        let parent = comment["in_reply_to_id"].as_u64().filter(|parent| ids.contains(parent)).unwrap_or(0);
+        threads.entry(parent).or_default().push(id);
+    }
+    threads
+}
+
+/// The most comments in a chain of replies starting at `root_id`, including it.
+fn compute_thread_depth(threads: &HashMap<u64, Vec<u64>>, root_id: u64) -> u32 {
+    let replies = threads.get(&root_id).map(Vec::as_slice).unwrap_or_default();
+    1 + replies.iter().map(|&reply| compute_thread_depth(threads, reply)).max().unwrap_or(0)
+}
+
+/// Leave out the reviews without a summary, unless `count_empty`. A review without one, like a bare approval, says
+/// nothing that would count as a comment.
+fn retain_counted_reviews(reviews: &mut Vec<Value>, count_empty: bool) {
+    if !count_empty {
+        reviews.retain(|review| !review["body"].as_str().unwrap_or_default().trim().is_empty());
+    }
+}
+
+/// How many times a PR was reviewed again, approving or requesting changes, after changes were requested.
+fn count_review_cycles(reviews: &[Value]) -> u32 {
+    let mut reviews: Vec<&Value> = reviews.iter().collect();
+    reviews.sort_by_key(|review| comment_created_at(review));
+    let mut changes_requested = false;
+    let mut cycles = 0;
+    for state in reviews.iter().filter_map(|review| review["state"].as_str()) {
+        if !matches!(state, "APPROVED" | "CHANGES_REQUESTED") {
+            continue;
+        }
+        if changes_requested {
+            cycles += 1;
+        }
+        changes_requested = state == "CHANGES_REQUESTED";
+    }
+    cycles
+}
+
+/// The time from `pr_created` to the earliest of `comments`, which are those of the analyzed user, if any.
+fn first_response_time(pr_created: DateTime<Utc>, comments: &[&Value]) -> Option<chrono::Duration> {
+    let first = comments.iter().filter_map(|comment| comment_created_at(comment)).min()?;
+    Some((first - pr_created).max(chrono::Duration::zero()))
+}
+
+/// The words in the body of `comment`, which is 0 without a body. Quoted lines, code fences and link targets aren't
+/// words, nor is anything without a letter or digit, like emoji and markup such as `**` or `-`.
+fn word_count(comment: &Value) -> u32 {
+    let mut in_code_block = false;
+    let mut words = 0;
+    for line in comment["body"].as_str().unwrap_or_default().lines() {
+        let line = line.trim_start();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        // Quote replies repeat the words of others
+        if !in_code_block && line.starts_with('>') {
+            continue;
+        }
+        let text = if in_code_block { line.to_string() } else { strip_link_targets(line) };
+        words += text.split_whitespace().filter(|word| word.chars().any(char::is_alphanumeric)).count() as u32;
+    }
+    words
+}
+
+/// `line` without the targets of its links and images, so `[the docs](https://example.com/docs)` leaves `[the docs`.
+fn strip_link_targets(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some((start, end)) = rest.find("](").and_then(|start| Some((start, start + rest[start..].find(')')?))) {
+        text.push_str(&rest[..start]);
+        text.push(' ');
+        rest = &rest[end + 1..];
+    }
+    text + rest
+}
+
+/// The time from the latest of `comments`, which are those of one user, to `merged_at`.
+/// Comments after the merge are left out, and without a merge or comments before it, there is none.
+fn time_after_last_comment(merged_at: Option<DateTime<Utc>>, comments: &[&Value]) -> Option<chrono::Duration> {
+    let merged_at = merged_at?;
+    let last = comments
+        .iter()
+        .filter_map(|comment| comment_created_at(comment))
+        .filter(|created_at| *created_at <= merged_at)
+        .max()?;
+    Some(merged_at - last)
+}
+
+/// How many words the bodies of `comments` have.
+fn analyze_comment_bodies(comments: &[&Value]) -> BodyStats {
+    let words: Vec<u32> = comments.iter().map(|comment| word_count(comment)).collect();
+    let total_words = words.iter().sum::<u32>();
+    BodyStats {
+        comments: words.len() as u32,
+        total_words,
+        min_words: words.iter().copied().min().unwrap_or(0),
+        max_words: words.iter().copied().max().unwrap_or(0),
+        mean_words_per_comment: (!words.is_empty()).then(|| total_words as f64 / words.len() as f64),
+    }
+}
+
+/// The number of review comments per file they were made on.
+fn group_by_file(comments: &[&Value]) -> BTreeMap<String, u32> {
+    let mut by_file = BTreeMap::new();
+    for path in comments.iter().filter_map(|comment| comment["path"].as_str()) {
+        *by_file.entry(path.to_string()).or_insert(0) += 1;
+    }
+    by_file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_local_bound, Exclusions};
+    use serde_json::json;
+
+    #[test]
+    fn comments_without_a_position_are_outdated() {
+        let comments = [
+            json!({ "id": 1, "position": null }),
+            json!({ "id": 2, "position": 3 }),
+            json!({ "id": 3 }),
+            json!({ "id": 4, "position": null }),
+        ];
+        let comments: Vec<&Value> = comments.iter().collect();
+        let (outdated, current) = partition_outdated(&comments);
+        let ids = |comments: Vec<&Value>| comments.iter().filter_map(|comment| comment["id"].as_u64()).collect::<Vec<_>>();
+        assert_eq!(ids(outdated), [1, 4]);
+        assert_eq!(ids(current), [2, 3]);
+    }
+
+    #[test]
+    fn merge_latency_is_measured_from_the_last_comment_before_the_merge() {
+        let merged_at = "2024-01-02T00:00:00Z".parse().ok();
+        let comments = [
+            json!({ "created_at": "2024-01-01T20:00:00Z" }),
+            json!({ "created_at": "2024-01-01T22:30:00Z" }),
+            json!({ "created_at": "2024-01-03T00:00:00Z" }),
+        ];
+        let comments: Vec<&Value> = comments.iter().collect();
+        let latency = time_after_last_comment(merged_at, &comments);
+        assert_eq!(latency, Some(chrono::Duration::minutes(90)));
+
+        assert_eq!(time_after_last_comment(merged_at, &[]), None, "no comments");
+        assert_eq!(time_after_last_comment(merged_at, &comments[2..]), None, "only comments after the merge");
+        assert_eq!(time_after_last_comment(None, &comments), None, "closed without a merge");
+    }
+
+    #[test]
+    fn comments_are_matched_by_id_across_renames() {
+        let comment = |login: &str, id: Option<u64>| json!({ "user": { "login": login, "id": id, "type": "User" } });
+        let comments = [
+            comment("old-name", Some(7)),
+            comment("new-name", Some(7)),
+            comment("new-name", Some(8)),
+            comment("someone", Some(9)),
+            comment("new-name", None),
+        ];
+        let count = |user| {
+            let mut excluded = BTreeMap::new();
+            let filter = CommentFilter { date_range: DateRange::default(), min_words: 0, exclusions: &Exclusions::default(), anonymizer: None };
+            count_user_comments(&comments, user, &filter, &mut excluded, &ProgressBar::hidden())
+        };
+        // By id, the comments under both logins count, but not those of whoever took over the login, while a comment
+        // without an id falls back to the login
+        assert_eq!(count(("new-name", Some(7))), 3);
+        assert_eq!(count(("old-name", Some(7))), 2);
+        // Without an id on either side, the login decides
+        assert_eq!(count(("new-name", None)), 3);
+        assert_eq!(comment_author(&comments[0], &[("someone", Some(9)), ("new-name", Some(7))]), Some("new-name"));
+    }
+
+    #[test]
+    fn words_are_counted_without_markup() {
+        let words = |body: &str| word_count(&serde_json::json!({ "body": body }));
+        for (body, expected) in [
+            ("", 0),
+            ("Looks good to me", 4),
+            ("🎉 👍 🚀", 0),
+            ("LGTM 🎉", 1),
+            ("- [ ] done? — yes!", 2),
+            ("See [the docs](https://example.com/docs/a/b) and ![a diagram](https://example.com/d.png).", 6),
+            ("[one](https://a.example) [two](https://b.example)", 2),
+            ("[broken link](https://example.com", 2),
+            ("> Why not use a map?\n>> Indeed\nBecause order matters", 3),
+            ("```rust\nlet x = 1;\n> not a quote\n```\nDone", 7),
+            ("  ~~~\n[a](target) b\n~~~", 2),
+            ("```\nunterminated code fence", 3),
+        ] {
+            assert_eq!(words(body), expected, "{:?}", body);
+        }
+        assert_eq!(word_count(&serde_json::json!({ "body": null })), 0);
+    }
+
+    #[test]
+    fn link_targets_are_stripped() {
+        assert_eq!(strip_link_targets("[the docs](https://example.com/docs)"), "[the docs ");
+        assert_eq!(strip_link_targets("a [b](c) d ![e](f) g"), "a [b  d ![e  g");
+        assert_eq!(strip_link_targets("no links (here) [or] there"), "no links (here) [or] there");
+        assert_eq!(strip_link_targets("unclosed [a](b"), "unclosed [a](b");
+    }
+
+    #[test]
+    fn reviews_without_a_summary_only_count_with_count_empty_reviews() {
+        let reviews = vec![
+            json!({ "id": 1, "state": "APPROVED", "body": "" }),
+            json!({ "id": 2, "state": "APPROVED", "body": null }),
+            json!({ "id": 3, "state": "COMMENTED", "body": "Some nits inline" }),
+            json!({ "id": 4, "state": "CHANGES_REQUESTED", "body": "Please add tests" }),
+            json!({ "id": 5, "state": "CHANGES_REQUESTED", "body": "" }),
+            json!({ "id": 6, "state": "COMMENTED", "body": " \n\t" }),
+        ];
+        let ids = |count_empty| {
+            let mut reviews = reviews.clone();
+            retain_counted_reviews(&mut reviews, count_empty);
+            reviews.iter().filter_map(|review| review["id"].as_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(false), [3, 4]);
+        assert_eq!(ids(true), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn replies_form_threads_and_replies_to_missing_comments_start_their_own() {
+        let comments = [
+            json!({ "id": 1 }),
+            json!({ "id": 2, "in_reply_to_id": 1 }),
+            json!({ "id": 3, "in_reply_to_id": 2 }),
+            json!({ "id": 4, "in_reply_to_id": 1 }),
+            json!({ "id": 5, "in_reply_to_id": 99 }),
+            json!({ "in_reply_to_id": 1 }),
+        ];
+        let threads = build_comment_threads(&comments);
+        assert_eq!(threads[&0], [1, 5], "the reply to a comment that isn't there is top-level");
+        assert_eq!(threads[&1], [2, 4], "comments without an id are skipped");
+        assert_eq!(threads[&2], [3]);
+        assert_eq!(compute_thread_depth(&threads, 1), 3);
+        assert_eq!(compute_thread_depth(&threads, 5), 1);
+        assert_eq!(compute_thread_depth(&threads, 0), 4, "the root is no comment, but adds one");
+
+        assert_eq!(build_comment_threads(&[]), HashMap::from([(0, Vec::new())]));
+    }
+
+    #[test]
+    fn review_cycles_are_counted_in_the_order_reviews_were_submitted() {
+        let review = |state: &str, day: u32| json!({ "state": state, "submitted_at": format!("2024-01-{:02}T00:00:00Z", day) });
+        assert_eq!(count_review_cycles(&[review("CHANGES_REQUESTED", 1), review("APPROVED", 2)]), 1);
+        let reviews = [
+            review("CHANGES_REQUESTED", 1),
+            review("COMMENTED", 2),
+            review("CHANGES_REQUESTED", 3),
+            review("APPROVED", 4),
+        ];
+        assert_eq!(count_review_cycles(&reviews), 2, "comments neither start nor end a cycle");
+        assert_eq!(count_review_cycles(&[review("APPROVED", 2), review("CHANGES_REQUESTED", 1)]), 1);
+        assert_eq!(count_review_cycles(&[review("CHANGES_REQUESTED", 2), review("APPROVED", 1)]), 0);
+        assert_eq!(count_review_cycles(&[]), 0);
+    }
+
+    #[test]
+    fn comments_on_the_bounds_count_only_on_since() -> Result<()> {
+        let comment = |created_at: &str| json!({ "user": { "login": "alice" }, "created_at": created_at });
+        let comments = [
+            comment("2024-03-04T23:59:59Z"),
+            comment("2024-03-05T00:00:00Z"),
+            comment("2024-03-06T23:59:59Z"),
+            comment("2024-03-07T00:00:00Z"),
+        ];
+        let date_range = DateRange {
+            since: Some(parse_local_bound("2024-03-05Z", false)?),
+            until: Some(parse_local_bound("2024-03-06Z", true)?),
+        };
+        let filter = CommentFilter { date_range, min_words: 0, exclusions: &Exclusions::default(), anonymizer: None };
+        let counted = user_comments(&comments, &[("alice", None)], &filter, &mut BTreeMap::new(), &ProgressBar::hidden());
+        let times: Vec<&str> = counted.iter().filter_map(|comment| comment["created_at"].as_str()).collect();
+        assert_eq!(times, ["2024-03-05T00:00:00Z", "2024-03-06T23:59:59Z"]);
+        Ok(())
+    }
+}
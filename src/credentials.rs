@@ -0,0 +1,121 @@
+//! Persisting GitHub tokens in the platform keyring, keyed by API host.
+
+use anyhow::{Context, Result};
+
+/// The keyring service name all tokens are stored under.
+const SERVICE: &str = "pr-comment-analyzer";
+
+/// The host used when none is given explicitly.
+pub const DEFAULT_HOST: &str = "github.com";
+
+/// A place to save, look up and remove one token per host.
+pub trait CredentialStore {
+    /// Return the token stored for `host`, if any.
+    fn get(&self, host: &str) -> Result<Option<String>>;
+    /// Store `token` for `host`, replacing any previous token.
+    fn set(&self, host: &str, token: &str) -> Result<()>;
+    /// Remove the token for `host`, returning `false` if there was none.
+    fn delete(&self, host: &str) -> Result<bool>;
+}
+
+/// The operating system's credential store: Keychain, Credential Manager or the Secret Service.
+pub struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(host: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(SERVICE, host).context("Failed to access the system keyring")
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn get(&self, host: &str) -> Result<Option<String>> {
+        match Self::entry(host)?.get_password() {
+            Ok(token) => Ok(Some(token)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context("Failed to read token from the system keyring"),
+        }
+    }
+
+    fn set(&self, host: &str, token: &str) -> Result<()> {
+        Self::entry(host)?
+            .set_password(token)
+            .context("Failed to store token in the system keyring")
+    }
+
+    fn delete(&self, host: &str) -> Result<bool> {
+        match Self::entry(host)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(e).context("Failed to delete token from the system keyring"),
+        }
+    }
+}
+
+/// Whether `error` means there is no usable keyring at all, as on a headless machine without a Secret Service,
+/// rather than that a lookup in it failed.
+pub fn is_unavailable(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<keyring::Error>(),
+        Some(keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_))
+    )
+}
+
+/// Keeps tokens in memory, standing in for the keyring in tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    tokens: std::sync::Mutex<std::collections::HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl CredentialStore for MemoryStore {
+    fn get(&self, host: &str) -> Result<Option<String>> {
+        Ok(self.tokens.lock().unwrap().get(host).cloned())
+    }
+
+    fn set(&self, host: &str, token: &str) -> Result<()> {
+        self.tokens.lock().unwrap().insert(host.to_string(), token.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, host: &str) -> Result<bool> {
+        Ok(self.tokens.lock().unwrap().remove(host).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_kept_per_host() -> Result<()> {
+        let store = MemoryStore::default();
+        store.set(DEFAULT_HOST, "public")?;
+        store.set("ghe.corp", "enterprise")?;
+        assert_eq!(store.get(DEFAULT_HOST)?.as_deref(), Some("public"));
+        assert_eq!(store.get("ghe.corp")?.as_deref(), Some("enterprise"));
+        assert_eq!(store.get("other.corp")?, None);
+
+        store.set("ghe.corp", "rotated")?;
+        assert_eq!(store.get("ghe.corp")?.as_deref(), Some("rotated"));
+
+        assert!(store.delete("ghe.corp")?);
+        assert!(!store.delete("ghe.corp")?);
+        assert_eq!(store.get("ghe.corp")?, None);
+        assert_eq!(store.get(DEFAULT_HOST)?.as_deref(), Some("public"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_keyrings_are_told_apart_from_failed_lookups() {
+        let missing = anyhow::Error::new(keyring::Error::NoStorageAccess("no Secret Service".into()))
+            .context("Failed to read token from the system keyring");
+        assert!(is_unavailable(&missing));
+        let platform = anyhow::Error::new(keyring::Error::PlatformFailure("no D-Bus session".into()));
+        assert!(is_unavailable(&platform));
+        let failed = anyhow::Error::new(keyring::Error::BadEncoding(vec![0xff]))
+            .context("Failed to read token from the system keyring");
+        assert!(!is_unavailable(&failed));
+        assert!(!is_unavailable(&anyhow::anyhow!("Failed to read token from the system keyring")));
+    }
+}
@@ -0,0 +1,227 @@
+//! Obtaining a token through GitHub's OAuth device authorization grant.
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Scopes requested for the token; `repo` is needed to read comments on private repositories.
+const SCOPES: &str = "repo";
+
+const DEVICE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(Debug, Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+    interval: Option<u64>,
+}
+
+/// Walk the user through the device flow for the OAuth app `client_id` on the server at `base_url`, like
+/// `https://github.com`, and return the granted token.
+pub async fn request_token(client: &Client, base_url: &str, client_id: &str) -> Result<String> {
+    let response = client
+        .post(format!("{}/login/device/code", base_url))
+        .header("Accept", "application/json")
+        .header("User-Agent", "pr-comment-analyzer")
+        .form(&[("client_id", client_id), ("scope", SCOPES)])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Failed to request a device code: {}", response.status()));
+    }
+    let device: DeviceCode = response
+        .json()
+        .await
+        .context("Unexpected response when requesting a device code. Is the client ID correct and device flow enabled for the app?")?;
+
+    eprintln!("Open {} and enter the code {}", device.verification_uri, device.user_code);
+
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval.max(1));
+
+    loop {
+        tokio::time::sleep(interval).await;
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("The device code expired before it was authorized. Please run login again."));
+        }
+
+        let response = client
+            .post(format!("{}/login/oauth/access_token", base_url))
+            .header("Accept", "application/json")
+            .header("User-Agent", "pr-comment-analyzer")
+            .form(&[
+                ("client_id", client_id),
+                ("device_code", device.device_code.as_str()),
+                ("grant_type", DEVICE_GRANT_TYPE),
+            ])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Failed to poll for the access token: {}", response.status()));
+        }
+        let token: TokenResponse = response.json().await?;
+
+        if let Some(access_token) = token.access_token {
+            return Ok(access_token);
+        }
+
+        match token.error.as_deref() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => {
+                // GitHub sends the new minimum interval; otherwise back off by the 5 seconds RFC 8628 prescribes
+                interval = match token.interval {
+                    Some(seconds) => Duration::from_secs(seconds),
+                    None => interval + Duration::from_secs(5),
+                };
+            }
+            Some("expired_token") => {
+                return Err(anyhow::anyhow!("The device code expired before it was authorized. Please run login again."));
+            }
+            Some("access_denied") => {
+                return Err(anyhow::anyhow!("Authorization was denied."));
+            }
+            Some(error) => {
+                return Err(anyhow::anyhow!(
+                    "Device flow failed: {}",
+                    token.error_description.as_deref().unwrap_or(error)
+                ));
+            }
+            None => return Err(anyhow::anyhow!("Unexpected response while polling for the access token")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Serve a device code expiring after `expires_in` seconds, and answer polls with `polls` in turn, on a
+    /// thread of its own so the paused clock of the test doesn't hold it up.
+    /// Returns the base URL and the polls left unanswered.
+    fn serve(expires_in: u64, polls: Vec<Value>) -> (String, Arc<Mutex<VecDeque<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let polls = Arc::new(Mutex::new(VecDeque::from(polls)));
+        let remaining = polls.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                reader.read_exact(&mut vec![0; content_length]).unwrap();
+
+                let body = if request_line.contains("/login/device/code") {
+                    json!({
+                        "device_code": "device-code",
+                        "user_code": "ABCD-1234",
+                        "verification_uri": "https://github.com/login/device",
+                        "expires_in": expires_in,
+                        "interval": 1,
+                    })
+                } else {
+                    polls.lock().unwrap().pop_front().unwrap_or_else(pending)
+                }
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                (&stream).write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, remaining)
+    }
+
+    /// Run the device flow against `base_url`, returning the outcome and how long it took on the paused clock.
+    async fn request(base_url: &str) -> (Result<String>, Duration) {
+        // Idle connections would have their expiry timer fast-forwarded by the paused clock
+        let client = Client::builder().pool_max_idle_per_host(0).build().unwrap();
+        let start = Instant::now();
+        let token = request_token(&client, base_url, "client-id").await;
+        (token, start.elapsed())
+    }
+
+    fn pending() -> Value {
+        json!({ "error": "authorization_pending" })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_is_returned_once_authorized() {
+        let (url, polls) = serve(900, vec![pending(), pending(), json!({ "access_token": "gho_granted" })]);
+        let (token, elapsed) = request(&url).await;
+        assert_eq!(token.unwrap(), "gho_granted");
+        assert!(polls.lock().unwrap().is_empty());
+        assert_eq!(elapsed.as_secs(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_down_backs_off() {
+        // Without an interval in the response, 5 seconds are added to the 1 second interval
+        let (url, _) = serve(900, vec![json!({ "error": "slow_down" }), json!({ "access_token": "gho_granted" })]);
+        let (token, elapsed) = request(&url).await;
+        assert_eq!(token.unwrap(), "gho_granted");
+        assert_eq!(elapsed.as_secs(), 1 + 6);
+
+        let (url, _) = serve(900, vec![json!({ "error": "slow_down", "interval": 10 }), json!({ "access_token": "gho_granted" })]);
+        let (token, elapsed) = request(&url).await;
+        assert_eq!(token.unwrap(), "gho_granted");
+        assert_eq!(elapsed.as_secs(), 1 + 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_and_denied_codes_fail() {
+        let (url, _) = serve(900, vec![pending(), json!({ "error": "expired_token" })]);
+        let error = request(&url).await.0.unwrap_err();
+        assert!(error.to_string().contains("device code expired"), "{}", error);
+
+        let (url, _) = serve(900, vec![json!({ "error": "access_denied" })]);
+        let error = request(&url).await.0.unwrap_err();
+        assert_eq!(error.to_string(), "Authorization was denied.");
+
+        let (url, _) = serve(900, vec![json!({ "error": "unsupported_grant_type", "error_description": "Bad grant" })]);
+        let error = request(&url).await.0.unwrap_err();
+        assert_eq!(error.to_string(), "Device flow failed: Bad grant");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polling_stops_when_the_code_expires() {
+        let (url, polls) = serve(3, vec![pending(), pending(), pending(), pending()]);
+        let (token, elapsed) = request(&url).await;
+        assert!(token.unwrap_err().to_string().contains("device code expired"));
+        assert_eq!(polls.lock().unwrap().len(), 2, "only the polls before the deadline are made");
+        assert_eq!(elapsed.as_secs(), 3);
+    }
+}
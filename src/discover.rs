@@ -0,0 +1,840 @@
+//! Finding the PRs to analyze besides those given, by searching, from notifications, labels, milestones, merge
+//! dates, a project column or the open PRs, and leaving out those the options rule out.
+
+use crate::forge::Forge;
+use crate::github::GitHubClient;
+use crate::{get_paginated_comments, graphql, parse_link, request_failed, AnalysisOptions, Analyzed, DateRange, PrRef};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use indicatif::ProgressBar;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// The PRs to analyze and the repositories they lie in, with what is known about them from how they were found.
+#[derive(Default)]
+pub struct Discovered {
+    pub repositories: Vec<(String, String)>,
+    pub prs: Vec<PrRef>,
+    /// The ids of the unread notification threads the PRs were found in.
+    pub notification_threads: Vec<String>,
+    /// The PRs found by merge date, which are only reported if the user commented on them.
+    pub merged: HashSet<PrRef>,
+    /// The PRs found by their labels, whose labels need no further look.
+    pub labeled: HashSet<PrRef>,
+    /// The open PRs, listed with whether they are drafts.
+    pub listed_open: HashSet<PrRef>,
+    /// The PRs found with `--search`.
+    pub searched: HashSet<PrRef>,
+}
+
+impl Discovered {
+    /// Add `pr` unless it's there already, returning whether it was added.
+    fn add(&mut self, pr: PrRef) -> bool {
+        if self.prs.contains(&pr) {
+            return false;
+        }
+        self.prs.push(pr);
+        true
+    }
+
+    /// Add `pr` like [`Discovered::add()`], along with its repository if it's in none of the analyzed ones.
+    fn add_anywhere(&mut self, pr: PrRef) -> bool {
+        if self.prs.contains(&pr) {
+            return false;
+        }
+        if !self.repositories.iter().any(|(owner, repo)| *owner == pr.owner && *repo == pr.repo) {
+            self.repositories.push((pr.owner.clone(), pr.repo.clone()));
+        }
+        self.prs.push(pr);
+        true
+    }
+}
+
+/// The `given_prs` of `repositories`, followed by those found by each source the options ask for, in turn.
+pub async fn discover(
+    forge: &impl Forge,
+    repositories: &[(String, String)],
+    given_prs: &[PrRef],
+    analyzed: &Analyzed,
+    options: &AnalysisOptions,
+) -> Result<Discovered> {
+    let mut discovered = Discovered {
+        repositories: repositories.to_vec(),
+        prs: given_prs.to_vec(),
+        ..Discovered::default()
+    };
+    let analyzed_user = options.analyzed_name(analyzed);
+    if let Some(org) = &options.org {
+        let github = forge.github().context("--org is only available on GitHub")?;
+        from_org(github, &mut discovered, org, analyzed.single_user()?, &analyzed_user, options).await?;
+    }
+    if options.involves_me {
+        let github = forge.github().context("--involves-me is only available on GitHub")?;
+        involving(github, &mut discovered, analyzed.single_user()?, &analyzed_user, options).await?;
+    }
+    if options.from_notifications {
+        let github = forge.github().context("--from-notifications is only available on GitHub")?;
+        from_notifications(github, &mut discovered, options).await?;
+    }
+    // Labels are looked up with the issues API when they find the PRs, and otherwise filter the PRs from elsewhere
+    let other_sources = !given_prs.is_empty()
+        || options.org.is_some()
+        || options.involves_me
+        || options.from_notifications
+        || options.reviewed_since.is_some()
+        || options.search.is_some()
+        || options.project.is_some()
+        || options.open_prs;
+    let lists_labeled = !options.labels.is_empty() && (options.merged.is_some() || !other_sources);
+    if lists_labeled || options.milestone.is_some() {
+        let github = forge.github().context("--label and --milestone are only available on GitHub")?;
+        labeled_or_in_milestone(github, &mut discovered, options).await?;
+    } else if let Some(window) = options.merged {
+        let github = forge.github().context("--merged-since and --merged-until are only available on GitHub")?;
+        merged_in(github, &mut discovered, window, options).await?;
+    }
+    if let Some(since) = options.reviewed_since {
+        let github = forge.github().context("--reviewed-since is only available on GitHub")?;
+        let found = search_reviewed_prs(github, analyzed.single_user()?, &discovered.repositories, since).await?;
+        options.progress.print(format_args!("Found {} PRs reviewed or commented on by {}", found.len(), analyzed_user));
+        for pr in found {
+            discovered.add(pr);
+        }
+    }
+    if let Some(query) = &options.search {
+        let github = forge.github().context("--search is only available on GitHub")?;
+        let found = search_query_prs(github, query, &discovered.repositories).await?;
+        options.progress.print(format_args!("Found {} PRs with the search {:?}", found.len(), query));
+        for pr in found {
+            if discovered.add_anywhere(pr.clone()) {
+                discovered.searched.insert(pr);
+            }
+        }
+    }
+    if let (Some((owner, title)), Some(column)) = (&options.project, &options.project_column) {
+        let github = forge.github().context("--project is only available on GitHub")?;
+        from_project_column(github, &mut discovered, owner, title, column, options).await?;
+    }
+    if options.open_prs {
+        let github = forge.github().context("--open-prs is only available on GitHub")?;
+        open(github, &mut discovered, options).await?;
+    }
+    Ok(discovered)
+}
+
+/// Add the PRs `user_login` commented on in the repositories of `org`, along with their repositories.
+async fn from_org(
+    github: &GitHubClient,
+    discovered: &mut Discovered,
+    org: &str,
+    user_login: &str,
+    analyzed_user: &str,
+    options: &AnalysisOptions,
+) -> Result<()> {
+    let found = search_commented_prs(github, org, user_login, options.date_range.since, options.repo_limit).await?;
+    options.progress.print(format_args!("Found {} PRs in {} commented on by {}", found.len(), org, analyzed_user));
+    for pr in found {
+        discovered.add_anywhere(pr);
+    }
+    Ok(())
+}
+
+/// Add the PRs anywhere on GitHub that involve `user_login`, along with their repositories.
+async fn involving(
+    github: &GitHubClient,
+    discovered: &mut Discovered,
+    user_login: &str,
+    analyzed_user: &str,
+    options: &AnalysisOptions,
+) -> Result<()> {
+    let found = search_involved_prs(github, user_login, options.date_range).await?;
+    options.progress.print(format_args!("Found {} PRs involving {}", found.len(), analyzed_user));
+    for pr in found {
+        discovered.add_anywhere(pr);
+    }
+    Ok(())
+}
+
+/// Add the PRs of the unread notifications, along with their repositories, remembering the notification threads.
+async fn from_notifications(github: &GitHubClient, discovered: &mut Discovered, options: &AnalysisOptions) -> Result<()> {
+    let found = get_pr_numbers_from_notifications(github).await?;
+    options.progress.print(format_args!("Found {} PRs in unread notifications", found.len()));
+    for (pr, thread_id) in found {
+        discovered.notification_threads.push(thread_id);
+        discovered.add_anywhere(pr);
+    }
+    Ok(())
+}
+
+/// Add the PRs of each repository with all of the `--label`s and in the `--milestone`, if given, and merged in the
+/// window of `--merged-since` and `--merged-until`, if given.
+async fn labeled_or_in_milestone(github: &GitHubClient, discovered: &mut Discovered, options: &AnalysisOptions) -> Result<()> {
+    for (owner, repo) in discovered.repositories.clone() {
+        let mut filters = Vec::new();
+        let mut criteria = Vec::new();
+        if !options.labels.is_empty() {
+            filters.push(("labels", options.labels.join(",")));
+            criteria.push(format!("labeled {}", options.labels.join(" and ")));
+        }
+        if let Some(title) = &options.milestone {
+            filters.push(("milestone", find_milestone(github, &owner, &repo, title).await?.to_string()));
+            criteria.push(format!("in milestone {}", title));
+        }
+        if options.merged.is_some() {
+            criteria.push("merged in the window".to_string());
+        }
+        let found = find_issue_prs(github, &owner, &repo, &filters, options.merged).await?;
+        options.progress.print(format_args!("Found {} PRs {} in {}/{}", found.len(), criteria.join(", "), owner, repo));
+        for pr in found {
+            if discovered.add(pr.clone()) {
+                if options.merged.is_some() {
+                    discovered.merged.insert(pr.clone());
+                }
+                if !options.labels.is_empty() {
+                    discovered.labeled.insert(pr);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the PRs of each repository merged in `window`.
+async fn merged_in(github: &GitHubClient, discovered: &mut Discovered, window: DateRange, options: &AnalysisOptions) -> Result<()> {
+    for (owner, repo) in discovered.repositories.clone() {
+        let found = find_merged_prs(github, &owner, &repo, window, &options.bases).await?;
+        options.progress.print(format_args!("Found {} PRs merged in {}/{}", found.len(), owner, repo));
+        for pr in found {
+            if discovered.add(pr.clone()) {
+                discovered.merged.insert(pr);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the PRs in `column` of the project of `owner` titled `title`, as far as they lie in the analyzed repositories.
+async fn from_project_column(
+    github: &GitHubClient,
+    discovered: &mut Discovered,
+    owner: &str,
+    title: &str,
+    column: &str,
+    options: &AnalysisOptions,
+) -> Result<()> {
+    let (found, drafts) = graphql::get_project_column_prs(github, owner, title, column).await?;
+    if drafts > 0 {
+        options.progress.print(format_args!("Skipped {} draft issues in column {} of project {}", drafts, column, title));
+    }
+    let mut count = 0;
+    for found in found {
+        // Boards span repositories, but only the analyzed ones are of interest
+        let Some((owner, repo)) = discovered
+            .repositories
+            .iter()
+            .find(|(owner, repo)| owner.eq_ignore_ascii_case(&found.owner) && repo.eq_ignore_ascii_case(&found.repo))
+        else {
+            continue;
+        };
+        let pr = PrRef { owner: owner.clone(), repo: repo.clone(), number: found.number };
+        count += 1;
+        discovered.add(pr);
+    }
+    options.progress.print(format_args!("Found {} PRs in column {} of project {}", count, column, title));
+    Ok(())
+}
+
+/// Add the open PRs of each repository, with all of the `--label`s if given, and unless they are drafts with
+/// `--exclude-drafts`.
+async fn open(github: &GitHubClient, discovered: &mut Discovered, options: &AnalysisOptions) -> Result<()> {
+    for (owner, repo) in discovered.repositories.clone() {
+        let mut numbers = discover_open_prs(github, &owner, &repo, &options.bases, &options.labels).await?;
+        if options.exclude_drafts {
+            for (number, _) in numbers.iter().filter(|(_, draft)| *draft) {
+                options.progress.print(format_args!("Skipping PR #{} (draft)", number));
+            }
+            numbers.retain(|(_, draft)| !draft);
+        }
+        let list: Vec<String> = numbers.iter().map(|(number, _)| number.to_string()).collect();
+        options.progress.print(format_args!("Open PRs in {}/{}: {}", owner, repo, list.join(", ")));
+        for (number, _) in numbers {
+            let pr = PrRef { owner: owner.clone(), repo: repo.clone(), number };
+            if !options.labels.is_empty() {
+                discovered.labeled.insert(pr.clone());
+            }
+            discovered.listed_open.insert(pr.clone());
+            discovered.add(pr);
+        }
+    }
+    Ok(())
+}
+
+/// Leave out the PRs of `discovered` that are `--exclude`d, drafts with `--exclude-drafts`, lack one of the
+/// `--label`s, target none of the `--base`s or are by an author `--pr-author` doesn't match. PRs are looked up as
+/// far as that takes, and the `given_prs` are only left out for their base if they were found too.
+/// Returns the PRs that were looked up, as the pulls API describes them.
+pub async fn select(
+    forge: &impl Forge,
+    discovered: &mut Discovered,
+    given_prs: &[PrRef],
+    options: &AnalysisOptions,
+) -> Result<HashMap<PrRef, Value>> {
+    let progress = options.progress;
+    let pr_name = pr_namer(&discovered.repositories);
+    let (excluded, prs): (Vec<PrRef>, Vec<PrRef>) = discovered.prs.drain(..).partition(|pr| options.exclude.contains(pr));
+    discovered.prs = prs;
+    if !excluded.is_empty() {
+        let names: Vec<String> = excluded.iter().map(&pr_name).collect();
+        progress.print(format_args!("Excluded {} PRs: {}", excluded.len(), names.join(", ")));
+    }
+    // The authors and bases filtered by are those of the PRs themselves, as are the times the first response and
+    // merge latency are measured from; PRs missing from `pulls`, as with --dry-run, are kept
+    let needs_pull = |pr: &&PrRef| {
+        options.first_response
+            || options.merge_latency
+            || options.pr_author.is_some()
+            || !options.bases.is_empty()
+            || (!options.labels.is_empty() && !discovered.labeled.contains(*pr))
+            || (options.exclude_drafts && !discovered.listed_open.contains(*pr))
+    };
+    let unfiltered: Vec<PrRef> = discovered.prs.iter().filter(needs_pull).cloned().collect();
+    let pulls = match forge.github() {
+        Some(github) if !unfiltered.is_empty() => get_pulls(github, &unfiltered, options.concurrency).await?,
+        _ => HashMap::new(),
+    };
+    if options.exclude_drafts {
+        leave_out_drafts(&mut discovered.prs, &pulls, &discovered.listed_open, options);
+    }
+    if !options.labels.is_empty() {
+        leave_out_unlabeled(&mut discovered.prs, &pulls, &discovered.labeled, &pr_name, options);
+    }
+    if !options.bases.is_empty() {
+        leave_out_other_bases(&mut discovered.prs, &pulls, given_prs, options);
+    }
+    if options.pr_author.is_some() {
+        leave_out_other_authors(&mut discovered.prs, &pulls, given_prs, &pr_name, options);
+    }
+    Ok(pulls)
+}
+
+/// How to name a PR of `repositories` in messages, which is by number alone if there is just one.
+fn pr_namer(repositories: &[(String, String)]) -> impl Fn(&PrRef) -> String {
+    let single = repositories.len() == 1;
+    move |pr| match single {
+        true => format!("#{}", pr.number),
+        false => format!("{}/{}#{}", pr.owner, pr.repo, pr.number),
+    }
+}
+
+/// Leave out the drafts among the PRs that weren't listed as open PRs, which already left out theirs.
+fn leave_out_drafts(prs: &mut Vec<PrRef>, pulls: &HashMap<PrRef, Value>, listed_open: &HashSet<PrRef>, options: &AnalysisOptions) {
+    prs.retain(|pr| {
+        let draft = !listed_open.contains(pr) && pulls.get(pr).is_some_and(|pull| pull["draft"].as_bool() == Some(true));
+        if draft {
+            options.progress.print(format_args!("Skipping PR #{} (draft)", pr.number));
+        }
+        !draft
+    });
+}
+
+/// Leave out the PRs without all of the `--label`s, unless they were found by them.
+fn leave_out_unlabeled(
+    prs: &mut Vec<PrRef>,
+    pulls: &HashMap<PrRef, Value>,
+    labeled: &HashSet<PrRef>,
+    pr_name: &impl Fn(&PrRef) -> String,
+    options: &AnalysisOptions,
+) {
+    prs.retain(|pr| {
+        let Some(pull) = pulls.get(pr).filter(|pull| pull.get("labels").is_some() && !labeled.contains(pr)) else {
+            return true;
+        };
+        if pr_has_all_labels(pull, &options.labels) {
+            return true;
+        }
+        options.progress.print(format_args!("Skipping {}, which isn't labeled {}", pr_name(pr), options.labels.join(" and ")));
+        false
+    });
+}
+
+/// Leave out the found PRs targeting none of the `--base`s, while the `given_prs` are kept whatever their base.
+fn leave_out_other_bases(prs: &mut Vec<PrRef>, pulls: &HashMap<PrRef, Value>, given_prs: &[PrRef], options: &AnalysisOptions) {
+    let base_of = |pr: &PrRef| pulls.get(pr).and_then(|pull| pull["base"]["ref"].as_str());
+    let found = prs.len();
+    prs.retain(|pr| given_prs.contains(pr) || base_of(pr).is_none_or(|base| options.bases.iter().any(|branch| branch == base)));
+    if prs.len() < found {
+        options.progress.print(format_args!(
+            "Left out {} found PRs not targeting {}",
+            found - prs.len(),
+            options.bases.join(" or ")
+        ));
+    }
+}
+
+/// Leave out the PRs by authors `--pr-author` doesn't match, naming those that were given.
+fn leave_out_other_authors(
+    prs: &mut Vec<PrRef>,
+    pulls: &HashMap<PrRef, Value>,
+    given_prs: &[PrRef],
+    pr_name: &impl Fn(&PrRef) -> String,
+    options: &AnalysisOptions,
+) {
+    let Some(filter) = &options.pr_author else {
+        return;
+    };
+    let (anonymized, progress) = (options.anonymizer.is_some(), options.progress);
+    let mut discovered_skipped = 0;
+    prs.retain(|pr| {
+        let author = pulls.get(pr).and_then(|pull| pull["user"]["login"].as_str());
+        let Some(author) = author.filter(|author| !filter.matches(author)) else {
+            return true;
+        };
+        if given_prs.contains(pr) && anonymized {
+            progress.print(format_args!("Skipping {}, as its author doesn't match --pr-author", pr_name(pr)));
+        } else if given_prs.contains(pr) {
+            progress.print(format_args!("Skipping {} by {}, as --pr-author is {}", pr_name(pr), author, filter));
+        } else {
+            discovered_skipped += 1;
+        }
+        false
+    });
+    if discovered_skipped > 0 && anonymized {
+        progress.print(format_args!("Left out {} found PRs, as their authors don't match --pr-author", discovered_skipped));
+    } else if discovered_skipped > 0 {
+        progress.print(format_args!("Left out {} found PRs, as --pr-author is {}", discovered_skipped, filter));
+    }
+}
+
+/// The search API returns no more than this many results for a query.
+const SEARCH_RESULT_CAP: u64 = 1000;
+
+/// The pause between search result pages, as search allows only 30 requests per minute with a token.
+const SEARCH_PAGE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The PRs that `user_login` commented on, updated since `since`, in the `repo_limit` most recently pushed
+/// repositories of `org`, ordered by repository and number.
+async fn search_commented_prs(
+    github: &GitHubClient,
+    org: &str,
+    user_login: &str,
+    since: Option<DateTime<Utc>>,
+    repo_limit: usize,
+) -> Result<Vec<PrRef>> {
+    let mut prs = Vec::new();
+    for (index, repo) in discover_org_repos(github, org, repo_limit).await?.iter().enumerate() {
+        if index > 0 {
+            tokio::time::sleep(SEARCH_PAGE_INTERVAL).await;
+        }
+        let mut query = format!("commenter:{} repo:{}/{} type:pr", user_login, org, repo);
+        if let Some(since) = since {
+            query.push_str(&format!(" updated:>={}", since.format("%Y-%m-%dT%H:%M:%SZ")));
+        }
+        prs.extend(search_prs(github, &query, "Narrow it down with a later --since.").await?);
+    }
+    prs.sort_by(|a, b| (&a.owner, &a.repo, a.number).cmp(&(&b.owner, &b.repo, b.number)));
+    Ok(prs)
+}
+
+/// The PRs anywhere on GitHub that involve `user_login` as author, assignee, commenter or mention, updated in
+/// `window`, ordered by repository and number.
+async fn search_involved_prs(github: &GitHubClient, user_login: &str, window: DateRange) -> Result<Vec<PrRef>> {
+    let format = |time: DateTime<Utc>| time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let updated = match (window.since, window.until) {
+        (Some(since), Some(until)) => format!("{}..{}", format(since), format(until)),
+        (Some(since), None) => format!(">={}", format(since)),
+        (None, Some(until)) => format!("<{}", format(until)),
+        (None, None) => "*".to_string(),
+    };
+    let query = format!("involves:{} type:pr updated:{}", user_login, updated);
+    let mut prs = search_prs(github, &query, "Narrow it down with a later --since or an earlier --until.").await?;
+    prs.sort_by(|a, b| (&a.owner, &a.repo, a.number).cmp(&(&b.owner, &b.repo, b.number)));
+    Ok(prs)
+}
+
+/// The PRs of the unread notifications about threads the authenticated user participates in, ordered by repository
+/// and number, each with the id of its notification thread.
+async fn get_pr_numbers_from_notifications(github: &GitHubClient) -> Result<Vec<(PrRef, String)>> {
+    if github.is_anonymous() {
+        return Err(anyhow::anyhow!("--from-notifications needs a token, as notifications belong to a user"));
+    }
+    let url = format!("{}/notifications?participating=true", github.api_url());
+    let notifications = get_paginated_comments(github, &url, None, &ProgressBar::hidden())
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to list the notifications: {}. This needs a classic token with the notifications or repo scope.", e))?;
+    let mut prs: Vec<(PrRef, String)> = notifications
+        .iter()
+        .filter(|notification| notification["subject"]["type"] == "PullRequest")
+        .filter_map(|notification| {
+            // Like https://api.github.com/repos/OWNER/REPO/pulls/NUMBER
+            let url = notification["subject"]["url"].as_str()?;
+            let (_, path) = url.split_once("/repos/")?;
+            let [owner, repo, "pulls", number] = path.split('/').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            let pr = PrRef { owner: owner.to_string(), repo: repo.to_string(), number: number.parse().ok()? };
+            Some((pr, notification["id"].as_str()?.to_string()))
+        })
+        .collect();
+    prs.sort_by(|(a, _), (b, _)| (&a.owner, &a.repo, a.number).cmp(&(&b.owner, &b.repo, b.number)));
+    Ok(prs)
+}
+
+/// The names of the repositories of `org`, most recently pushed first, but no more than `limit`. Pages stop being
+/// fetched once there are enough.
+async fn discover_org_repos(github: &GitHubClient, org: &str, limit: usize) -> Result<Vec<String>> {
+    let per_page = limit.clamp(1, 100).to_string();
+    let mut url = format!("{}/orgs/{}/repos?sort=pushed&direction=desc", github.api_url(), org);
+    let mut names = Vec::new();
+    loop {
+        let response = github.get(&url, &[("per_page", &per_page)]).await?;
+        if !response.status().is_success() {
+            return Err(request_failed(github, &response));
+        }
+        let next_url = response
+            .headers()
+            .get("link")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| parse_link(header, "next"));
+        let repos: Vec<Value> = response.json().await?;
+        names.extend(repos.iter().filter_map(|repo| repo["name"].as_str().map(str::to_string)));
+        match next_url {
+            Some(next_url) if names.len() < limit => url = next_url,
+            next_url => {
+                if next_url.is_some() || names.len() > limit {
+                    eprintln!(
+                        "Warning: {} has more than {} repositories, only searching the {} most recently pushed. Raise the limit with --repo-limit.",
+                        org, limit, limit
+                    );
+                }
+                break;
+            }
+        }
+    }
+    names.truncate(limit);
+    Ok(names)
+}
+
+/// The PRs of `repositories` that `user_login` reviewed or commented on, updated since `since`, ordered by
+/// repository and number.
+async fn search_reviewed_prs(
+    github: &GitHubClient,
+    user_login: &str,
+    repositories: &[(String, String)],
+    since: DateTime<Utc>,
+) -> Result<Vec<PrRef>> {
+    let mut prs = Vec::new();
+    for (index, (owner, repo)) in repositories.iter().enumerate() {
+        // Reviews without a comment on the diff make someone a reviewer, but not a commenter
+        for (qualifier_index, qualifier) in ["reviewed-by", "commenter"].iter().enumerate() {
+            if index > 0 || qualifier_index > 0 {
+                tokio::time::sleep(SEARCH_PAGE_INTERVAL).await;
+            }
+            let query = format!(
+                "{}:{} repo:{}/{} updated:>={} type:pr",
+                qualifier,
+                user_login,
+                owner,
+                repo,
+                since.format("%Y-%m-%dT%H:%M:%SZ")
+            );
+            for pr in search_prs(github, &query, "Narrow it down with a later --reviewed-since.").await? {
+                if !prs.contains(&pr) {
+                    prs.push(pr);
+                }
+            }
+        }
+    }
+    prs.sort_by(|a, b| (&a.owner, &a.repo, a.number).cmp(&(&b.owner, &b.repo, b.number)));
+    Ok(prs)
+}
+
+/// The PRs found by the `--search` query in each of `repositories`, or wherever the query's own `repo:` points.
+/// Duplicates are removed, and the order is the search's, repository by repository.
+async fn search_query_prs(github: &GitHubClient, query: &str, repositories: &[(String, String)]) -> Result<Vec<PrRef>> {
+    let has_qualifier = |qualifier: &str| query.split_whitespace().any(|term| term.starts_with(qualifier));
+    let query = if has_qualifier("type:") || has_qualifier("is:pr") {
+        query.to_string()
+    } else {
+        format!("{} type:pr", query)
+    };
+    let queries: Vec<String> = if has_qualifier("repo:") {
+        vec![query]
+    } else {
+        repositories.iter().map(|(owner, repo)| format!("{} repo:{}/{}", query, owner, repo)).collect()
+    };
+    
+    let mut prs = Vec::new();
+    for query in queries {
+        for pr in search_prs(github, &query, "Narrow the query down, for instance by date.").await? {
+            if !prs.contains(&pr) {
+                prs.push(pr);
+            }
+        }
+    }
+    Ok(prs)
+}
+
+/// The PRs found by the search `query`, paged at the pace the search rate limit allows.
+/// Beyond `SEARCH_RESULT_CAP` results, warns and adds `hint` on how to find fewer.
+async fn search_prs(github: &GitHubClient, query: &str, hint: &str) -> Result<Vec<PrRef>> {
+    let url = format!("{}/search/issues", github.api_url());
+    
+    let mut prs = Vec::new();
+    let mut page = 1;
+    loop {
+        let page_number = page.to_string();
+        let response = github
+            .get(&url, &[("q", query), ("per_page", "100"), ("page", page_number.as_str())])
+            .await;
+        let response = match response {
+            Ok(response) if response.status().is_success() => response,
+            // Secondary rate limits strike deep into long searches; what was found so far is still worth analyzing
+            failed if page > 1 => {
+                let reason = match &failed {
+                    Ok(response) => request_failed(github, response),
+                    Err(e) => anyhow::anyhow!("{}", e),
+                };
+                eprintln!(
+                    "Warning: the search stopped at page {}, analyzing the {} PRs found so far: {}",
+                    page,
+                    prs.len(),
+                    reason
+                );
+                break;
+            }
+            Ok(response) => return Err(request_failed(github, &response)),
+            Err(e) => return Err(e),
+        };
+        
+        let has_next_page = response
+            .headers()
+            .get("link")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|header| parse_link(header, "next"))
+            .is_some();
+        let results: Value = response.json().await?;
+        let total_count = results["total_count"].as_u64().unwrap_or_default();
+        if page == 1 && total_count > SEARCH_RESULT_CAP {
+            eprintln!(
+                "Warning: the search found {} PRs, but only the first {} can be retrieved. {}",
+                total_count,
+                SEARCH_RESULT_CAP,
+                hint
+            );
+        }
+        
+        for item in results["items"].as_array().into_iter().flatten() {
+            // `repository_url` is the API URL of the repository, ending in /repos/{owner}/{repo}
+            let repository = item["repository_url"].as_str().and_then(|url| url.rsplit_once("/repos/"));
+            let Some((owner, repo)) = repository.and_then(|(_, repository)| repository.split_once('/')) else {
+                continue;
+            };
+            if let Some(number) = item["number"].as_u64().and_then(|number| u32::try_from(number).ok()) {
+                prs.push(PrRef {
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                    number,
+                });
+            }
+        }
+        
+        if !has_next_page {
+            break;
+        }
+        page += 1;
+        tokio::time::sleep(SEARCH_PAGE_INTERVAL).await;
+    }
+    Ok(prs)
+}
+
+/// The PRs of `owner/repo` merged within `window`, ordered by number, only those targeting one of `bases` if any.
+/// Closed PRs are listed by when they were last updated, which is never before they were merged, so listing stops
+/// at the first PR last updated before the window.
+async fn find_merged_prs(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    window: DateRange,
+    bases: &[String],
+) -> Result<Vec<PrRef>> {
+    let mut url = pulls_url(github, owner, repo, &[("state", "closed"), ("sort", "updated"), ("direction", "desc"), ("per_page", "100")], bases)?;
+    let mut prs = Vec::new();
+    loop {
+        let response = github.get(&url, &[]).await?;
+        if !response.status().is_success() {
+            return Err(request_failed(github, &response));
+        }
+        let next_url = response
+            .headers()
+            .get("link")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|header| parse_link(header, "next"));
+        
+        let pulls: Vec<Value> = response.json().await?;
+        let timestamp = |pull: &Value, field: &str| {
+            pull[field].as_str().and_then(|value| DateTime::parse_from_rfc3339(value).ok()).map(|time| time.with_timezone(&Utc))
+        };
+        let mut before_window = false;
+        for pull in &pulls {
+            if window.since.is_some_and(|since| timestamp(pull, "updated_at").is_some_and(|updated_at| updated_at < since)) {
+                before_window = true;
+                break;
+            }
+            let Some(merged_at) = timestamp(pull, "merged_at") else {
+                continue;
+            };
+            if !window.contains(merged_at) || !targets(pull, bases) {
+                continue;
+            }
+            if let Some(number) = pull["number"].as_u64().and_then(|number| u32::try_from(number).ok()) {
+                prs.push(PrRef { owner: owner.to_string(), repo: repo.to_string(), number });
+            }
+        }
+        
+        match next_url {
+            Some(next_url) if !before_window => url = next_url,
+            _ => break,
+        }
+    }
+    
+    prs.sort_by_key(|pr| pr.number);
+    Ok(prs)
+}
+
+/// The PRs of `owner/repo` the issues API lists with `filters`, like `labels` or `milestone`, ordered by number.
+/// With a `merged` window, only those merged in it.
+async fn find_issue_prs(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    filters: &[(&str, String)],
+    merged: Option<DateRange>,
+) -> Result<Vec<PrRef>> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/repos/{}/{}/issues", github.api_url(), owner, repo),
+        filters.iter().map(|(name, value)| (*name, value.as_str())).chain([("state", "all")]),
+    )?;
+    let issues = get_paginated_comments(github, url.as_str(), None, &ProgressBar::hidden()).await?;
+    
+    // Issues and PRs share the list; only PRs have `pull_request`
+    let mut prs: Vec<PrRef> = issues
+        .iter()
+        .filter(|issue| issue.get("pull_request").is_some())
+        .filter(|issue| {
+            merged.is_none_or(|window| {
+                let merged_at = issue["pull_request"]["merged_at"].as_str().and_then(|value| DateTime::parse_from_rfc3339(value).ok());
+                merged_at.is_some_and(|merged_at| window.contains(merged_at.with_timezone(&Utc)))
+            })
+        })
+        .filter_map(|issue| issue["number"].as_u64().and_then(|number| u32::try_from(number).ok()))
+        .map(|number| PrRef { owner: owner.to_string(), repo: repo.to_string(), number })
+        .collect();
+    prs.sort_by_key(|pr| pr.number);
+    Ok(prs)
+}
+
+/// The number of the milestone of `owner/repo` titled `title`, ignoring case unless that makes it ambiguous.
+/// Fails listing all milestones if there is no such milestone, or several.
+async fn find_milestone(github: &GitHubClient, owner: &str, repo: &str, title: &str) -> Result<u64> {
+    let url = format!("{}/repos/{}/{}/milestones?state=all", github.api_url(), owner, repo);
+    let milestones = get_paginated_comments(github, &url, None, &ProgressBar::hidden()).await?;
+    let titled = |matches: &dyn Fn(&str) -> bool| -> Vec<u64> {
+        milestones
+            .iter()
+            .filter(|milestone| milestone["title"].as_str().is_some_and(matches))
+            .filter_map(|milestone| milestone["number"].as_u64())
+            .collect()
+    };
+    let mut numbers = titled(&|candidate| candidate == title);
+    if numbers.is_empty() {
+        numbers = titled(&|candidate| candidate.eq_ignore_ascii_case(title));
+    }
+    if let [number] = numbers.as_slice() {
+        return Ok(*number);
+    }
+    
+    let available: Vec<String> = milestones
+        .iter()
+        .filter_map(|milestone| milestone["title"].as_str())
+        .map(|title| format!("  {}", title))
+        .collect();
+    let problem = if numbers.is_empty() { "no milestone" } else { "several milestones" };
+    if available.is_empty() {
+        return Err(anyhow::anyhow!("{}/{} has {} titled {}, and no milestones at all", owner, repo, problem, title));
+    }
+    Err(anyhow::anyhow!(
+        "{}/{} has {} titled {}. Its milestones are:\n{}",
+        owner,
+        repo,
+        problem,
+        title,
+        available.join("\n")
+    ))
+}
+
+/// Each of `prs` as the pulls API describes it, with its author and base, fetching up to `concurrency` at once.
+async fn get_pulls(github: &GitHubClient, prs: &[PrRef], concurrency: usize) -> Result<HashMap<PrRef, Value>> {
+    let pull = |pr: PrRef| async move {
+        let url = format!("{}/repos/{}/{}/pulls/{}", github.api_url(), pr.owner, pr.repo, pr.number);
+        let response = github.get(&url, &[]).await?;
+        if !response.status().is_success() {
+            return Err(request_failed(github, &response));
+        }
+        Ok((pr, response.json::<Value>().await?))
+    };
+    futures::stream::iter(prs.iter().cloned())
+        .map(pull)
+        .buffered(concurrency)
+        .collect::<Vec<Result<_>>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The numbers of the open PRs of `owner/repo`, oldest first, each with whether it is a draft, only those
+/// targeting one of `bases` and with all of `labels`, if any.
+async fn discover_open_prs(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    bases: &[String],
+    labels: &[String],
+) -> Result<Vec<(u32, bool)>> {
+    let url = pulls_url(github, owner, repo, &[("state", "open"), ("sort", "created"), ("direction", "asc")], bases)?;
+    let pulls = get_paginated_comments(github, &url, None, &ProgressBar::hidden()).await?;
+    Ok(pulls
+        .iter()
+        .filter(|pull| targets(pull, bases) && pr_has_all_labels(pull, labels))
+        .filter_map(|pull| {
+            let number = pull["number"].as_u64().and_then(|number| u32::try_from(number).ok())?;
+            Some((number, pull["draft"].as_bool() == Some(true)))
+        })
+        .collect())
+}
+
+/// The URL listing the PRs of `owner/repo` with `params`. The list can only be filtered by a single base, so with
+/// several `bases`, it's up to the caller to pick those with `targets`.
+fn pulls_url(github: &GitHubClient, owner: &str, repo: &str, params: &[(&str, &str)], bases: &[String]) -> Result<String> {
+    let base = match bases {
+        [base] => Some(("base", base.as_str())),
+        _ => None,
+    };
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/repos/{}/{}/pulls", github.api_url(), owner, repo),
+        params.iter().copied().chain(base),
+    )?;
+    Ok(url.into())
+}
+
+/// Whether the PR `pr` from the pulls API has all of the `required` labels, whose case GitHub ignores.
+fn pr_has_all_labels(pr: &Value, required: &[String]) -> bool {
+    let labels: Vec<&str> = pr["labels"].as_array().into_iter().flatten().filter_map(|label| label["name"].as_str()).collect();
+    required.iter().all(|name| labels.iter().any(|label| label.eq_ignore_ascii_case(name)))
+}
+
+/// Whether the PR `pull` from the pulls API targets one of `bases`, or there are none to target.
+fn targets(pull: &Value, bases: &[String]) -> bool {
+    bases.is_empty() || pull["base"]["ref"].as_str().is_some_and(|base| bases.iter().any(|branch| branch == base))
+}
@@ -0,0 +1,33 @@
+//! Printing the requests an analysis would send, for `--dry-run`.
+
+use crate::github::HttpClient;
+use futures::future::BoxFuture;
+use reqwest::{Request, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Prints the URL of each request to stdout instead of sending it, answering with an empty list.
+#[derive(Default)]
+pub struct DryRunClient {
+    requests: AtomicUsize,
+}
+
+impl DryRunClient {
+    /// The number of requests that would have been sent so far.
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl HttpClient for DryRunClient {
+    fn send(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+        println!("{}", request.url());
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        // Without a `Link` header, every list ends after its first page
+        let response = http::Response::builder()
+            .status(200)
+            .header("content-type", "application/json")
+            .body("[]")
+            .expect("the stubbed response is valid");
+        Box::pin(async move { Ok(Response::from(response)) })
+    }
+}
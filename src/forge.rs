@@ -0,0 +1,53 @@
+//! The code hosting services whose PRs, or merge requests as GitLab calls them, can be analyzed.
+
+use crate::github::GitHubClient;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use indicatif::{MultiProgress, ProgressBar};
+use serde_json::Value;
+
+/// The service the analyzed repositories are hosted on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
+/// The comments of one PR, shaped like the GitHub REST API responses so they are counted the same way.
+#[derive(Default)]
+pub struct PrCommentData {
+    /// Review comments on the diff, as from `/pulls/{n}/comments`.
+    pub pr_comments: Vec<Value>,
+    /// Reviews, as from `/pulls/{n}/reviews`.
+    pub review_comments: Vec<Value>,
+    /// Conversation comments, as from `/issues/{n}/comments`.
+    pub issue_comments: Vec<Value>,
+}
+
+/// What the analysis needs from a code hosting service.
+pub trait Forge {
+    /// The login of the user the credentials belong to.
+    async fn authenticated_user(&self) -> Result<String>;
+
+    /// All comments of PR `number` in `owner/repo`. Comments outside of `since` and `until` may be left out.
+    async fn pr_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        bar: &ProgressBar,
+    ) -> Result<PrCommentData>;
+
+    /// The web page of PR `number` in `repository`.
+    fn pr_url(&self, repository: &str, number: u32) -> String;
+
+    /// Print warnings above `progress` instead of straight to stderr, where they would garble the bars.
+    fn set_progress(&mut self, _progress: Option<MultiProgress>) {}
+
+    /// The GitHub client, for what only GitHub has: discussions, commit comments and searching organizations.
+    fn github(&self) -> Option<&GitHubClient> {
+        None
+    }
+}
@@ -0,0 +1,58 @@
+//! Optional ANSI colors for the text report.
+
+use owo_colors::OwoColorize;
+use std::env;
+use std::fmt::Display;
+
+/// Renders report fragments, with or without colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Formatter {
+    color: bool,
+}
+
+impl Formatter {
+    pub fn new(color: bool) -> Self {
+        Formatter { color }
+    }
+
+    /// Use colors only when writing to a terminal, unless disabled by `--no-color`,
+    /// a non-empty `NO_COLOR` (https://no-color.org) or `TERM=dumb`.
+    pub fn detect(no_color_flag: bool, is_terminal: bool) -> Self {
+        let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let dumb_terminal = env::var("TERM").is_ok_and(|term| term == "dumb");
+        Formatter::new(is_terminal && !no_color_flag && !no_color_env && !dumb_terminal)
+    }
+
+    pub fn pr_number(&self, pr_number: u32) -> String {
+        let text = format!("#{}", pr_number);
+        if self.color {
+            text.cyan().to_string()
+        } else {
+            text
+        }
+    }
+
+    pub fn count(&self, count: impl Display) -> String {
+        if self.color {
+            count.green().to_string()
+        } else {
+            count.to_string()
+        }
+    }
+
+    pub fn heading(&self, text: &str) -> String {
+        if self.color {
+            text.bold().yellow().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn error(&self, text: impl Display) -> String {
+        if self.color {
+            text.red().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,612 @@
+//! Authenticated access to the GitHub REST API, retrying transient failures.
+
+use crate::github_app::{GitHubApp, InstallationToken};
+use crate::secret::{self, Secret};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use indicatif::MultiProgress;
+use reqwest::header::{HeaderValue, AUTHORIZATION, USER_AGENT};
+use futures::future::BoxFuture;
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Statuses GitHub returns under load that are worth retrying.
+const RETRYABLE_STATUSES: [StatusCode; 5] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// The REST API version all requests are pinned to.
+const API_VERSION: &str = "2022-11-28";
+
+/// Whether this runs in a GitHub Actions workflow, which describes its repository and hosts in the environment.
+pub fn in_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+/// The root of the REST API unless `--api-url` names another: `GITHUB_API_URL` in GitHub Actions, so workflows on
+/// GitHub Enterprise Server talk to their own host, or else api.github.com.
+pub fn default_api_url() -> String {
+    actions_url("GITHUB_API_URL").unwrap_or_else(|| "https://api.github.com".into())
+}
+
+/// The GraphQL endpoint next to the REST API at `api_url`: `GITHUB_GRAPHQL_URL` in GitHub Actions, or else the
+/// one of the API's host, like `https://HOSTNAME/api/graphql` for `https://HOSTNAME/api/v3`.
+pub fn graphql_url(api_url: &str) -> String {
+    if api_url == default_api_url() {
+        return actions_url("GITHUB_GRAPHQL_URL").unwrap_or_else(|| "https://api.github.com/graphql".into());
+    }
+    match api_url.strip_suffix("/v3") {
+        Some(api) => format!("{}/graphql", api),
+        None => format!("{}/graphql", api_url),
+    }
+}
+
+/// The web host PRs are shown on for the REST API at `api_url`: `GITHUB_SERVER_URL` in GitHub Actions, github.com,
+/// or the host of a GitHub Enterprise Server.
+pub fn server_url(api_url: &str) -> String {
+    if api_url == default_api_url() {
+        return actions_url("GITHUB_SERVER_URL").unwrap_or_else(|| "https://github.com".into());
+    }
+    let url = api_url.strip_suffix("/api/v3").unwrap_or(api_url);
+    url.replacen("://api.", "://", 1)
+}
+
+/// The host of `server_url`, like github.com, which tokens are stored under with `auth login --hostname`.
+pub fn host(api_url: &str) -> String {
+    let url = server_url(api_url);
+    let host = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    host.split('/').next().unwrap_or(host).to_string()
+}
+
+fn actions_url(variable: &str) -> Option<String> {
+    in_github_actions()
+        .then(|| env::var(variable).ok())
+        .flatten()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Prefixes of classic personal access tokens and OAuth app tokens, the only ones sent with the `token` scheme.
+const CLASSIC_TOKEN_PREFIXES: [&str; 2] = ["ghp_", "gho_"];
+
+/// The delay before the first retry, doubled for every further attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A token taking part in rotation, with what the API last told us about its rate limit.
+struct PooledToken {
+    token: Secret,
+    /// `X-RateLimit-Remaining` from the last response, or `u32::MAX` before the first one.
+    remaining: AtomicU32,
+    requests: AtomicU32,
+}
+
+/// Tokens used in turn, moving on to the next once the current one's remaining requests drop below `floor`.
+struct TokenPool {
+    tokens: Vec<PooledToken>,
+    current: AtomicUsize,
+    floor: u32,
+}
+
+impl TokenPool {
+    fn new(tokens: Vec<Secret>, floor: u32) -> Self {
+        TokenPool {
+            tokens: tokens
+                .into_iter()
+                .map(|token| PooledToken {
+                    token,
+                    remaining: AtomicU32::new(u32::MAX),
+                    requests: AtomicU32::new(0),
+                })
+                .collect(),
+            current: AtomicUsize::new(0),
+            floor,
+        }
+    }
+
+    /// The index of the token to use next, rotating away from the current one if it is running low.
+    fn select(&self) -> usize {
+        let current = self.current.load(Ordering::Relaxed);
+        if self.tokens[current].remaining.load(Ordering::Relaxed) >= self.floor {
+            return current;
+        }
+
+        let next = (1..self.tokens.len())
+            .map(|offset| (current + offset) % self.tokens.len())
+            .find(|&index| self.tokens[index].remaining.load(Ordering::Relaxed) >= self.floor)
+            // All tokens are low: use whichever has the most requests left
+            .unwrap_or_else(|| {
+                (0..self.tokens.len())
+                    .max_by_key(|&index| self.tokens[index].remaining.load(Ordering::Relaxed))
+                    .unwrap_or(current)
+            });
+        if next != current {
+            tracing::debug!("Rotating to token {} of {}", next + 1, self.tokens.len());
+        }
+        self.current.store(next, Ordering::Relaxed);
+        next
+    }
+
+    fn record(&self, index: usize, response: &Response) {
+        let token = &self.tokens[index];
+        token.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(remaining) = rate_limit_remaining(response) {
+            token.remaining.store(remaining, Ordering::Relaxed);
+        }
+    }
+}
+
+/// How many requests were sent with one token.
+#[derive(Debug, Serialize)]
+pub struct TokenUsage {
+    pub token: String,
+    pub requests: u32,
+}
+
+/// What to do when the primary rate limit runs low or is exhausted.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    /// Sleep until the limit resets instead of failing.
+    pub wait: bool,
+    /// Warn before each request once fewer than this many requests are left.
+    pub warn_below: u32,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        RateLimitPolicy {
+            wait: true,
+            warn_below: 10,
+        }
+    }
+}
+
+/// Sends the requests `GitHubClient` prepared.
+pub trait HttpClient: Send + Sync {
+    /// Send `request`, resolving to its response.
+    fn send(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>>;
+}
+
+impl HttpClient for Client {
+    fn send(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+        Box::pin(self.execute(request))
+    }
+}
+
+/// How requests are authenticated.
+enum Credentials {
+    Anonymous,
+    Tokens(TokenPool),
+    /// An app installation, whose short-lived token is re-minted as it nears expiry.
+    App {
+        app: GitHubApp,
+        current: Mutex<Option<InstallationToken>>,
+    },
+}
+
+/// An HTTP client bound to credentials, used for all REST API calls.
+pub struct GitHubClient {
+    client: Client,
+    /// Where requests are sent, `client` unless replaced with `with_http`.
+    http: Arc<dyn HttpClient>,
+    credentials: Credentials,
+    max_retries: u32,
+    rate_limit: RateLimitPolicy,
+    /// `X-RateLimit-Remaining` from the last response, or `u32::MAX` before the first one.
+    last_remaining: AtomicU32,
+    /// Progress bars that warnings are printed above, if any are shown.
+    progress: Option<MultiProgress>,
+    /// The root of the REST API, without a trailing slash.
+    api_url: String,
+}
+
+impl GitHubClient {
+    pub fn new(client: Client, token: Secret, max_retries: u32) -> Self {
+        GitHubClient::with_tokens(client, vec![token], 0, max_retries)
+    }
+
+    /// Rotate across `tokens`, switching once the current one has fewer than `floor` requests left.
+    pub fn with_tokens(client: Client, tokens: Vec<Secret>, floor: u32, max_retries: u32) -> Self {
+        GitHubClient {
+            http: Arc::new(client.clone()),
+            client,
+            credentials: Credentials::Tokens(TokenPool::new(tokens, floor)),
+            max_retries,
+            rate_limit: RateLimitPolicy::default(),
+            last_remaining: AtomicU32::new(u32::MAX),
+            progress: None,
+            api_url: default_api_url(),
+        }
+    }
+
+    pub fn with_rate_limit_policy(mut self, rate_limit: RateLimitPolicy) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Send requests with `http` instead of over the network.
+    pub fn with_http(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Send requests to the REST API at `api_url`, like `https://HOSTNAME/api/v3` for a GitHub Enterprise Server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// The root of the REST API requests are sent to.
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// The GraphQL endpoint next to the REST API.
+    pub fn graphql_url(&self) -> String {
+        graphql_url(&self.api_url)
+    }
+
+    /// The web host PRs are shown on.
+    pub fn server_url(&self) -> String {
+        server_url(&self.api_url)
+    }
+
+    /// Print warnings above `progress` instead of straight to stderr, where they would garble the bars.
+    pub fn set_progress(&mut self, progress: Option<MultiProgress>) {
+        self.progress = progress;
+    }
+
+    /// Whether the last response left fewer requests than the rate limit policy warns about.
+    pub fn rate_limit_low(&self) -> bool {
+        self.last_remaining.load(Ordering::Relaxed) < self.rate_limit.warn_below
+    }
+
+    /// Send requests without credentials, which only works for public repositories at a low rate limit.
+    pub fn anonymous(client: Client, max_retries: u32) -> Self {
+        GitHubClient {
+            http: Arc::new(client.clone()),
+            client,
+            credentials: Credentials::Anonymous,
+            max_retries,
+            rate_limit: RateLimitPolicy::default(),
+            last_remaining: AtomicU32::new(u32::MAX),
+            progress: None,
+            api_url: default_api_url(),
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        matches!(self.credentials, Credentials::Anonymous)
+    }
+
+    /// A client using only the token at `index` of the rotation, for checks that have to be made per token.
+    pub fn single(&self, index: usize) -> Option<GitHubClient> {
+        match &self.credentials {
+            Credentials::Tokens(pool) => pool
+                .tokens
+                .get(index)
+                .map(|token| {
+                    GitHubClient::new(self.client.clone(), token.token.clone(), self.max_retries)
+                        .with_rate_limit_policy(self.rate_limit)
+                        .with_http(self.http.clone())
+                        .with_api_url(&self.api_url)
+                }),
+            Credentials::Anonymous | Credentials::App { .. } => None,
+        }
+    }
+
+    /// The number of tokens in rotation; zero for app installations and anonymous access.
+    pub fn token_count(&self) -> usize {
+        match &self.credentials {
+            Credentials::Tokens(pool) => pool.tokens.len(),
+            Credentials::Anonymous | Credentials::App { .. } => 0,
+        }
+    }
+
+    /// Requests sent with each rotated token so far, identifying tokens by their mask.
+    pub fn token_usage(&self) -> Vec<TokenUsage> {
+        match &self.credentials {
+            Credentials::Tokens(pool) => pool
+                .tokens
+                .iter()
+                .map(|token| TokenUsage {
+                    token: token.token.masked(),
+                    requests: token.requests.load(Ordering::Relaxed),
+                })
+                .collect(),
+            Credentials::Anonymous | Credentials::App { .. } => Vec::new(),
+        }
+    }
+
+    /// Authenticate as an installation of `app`, whose installation must already be known.
+    pub fn for_app(client: Client, app: GitHubApp, max_retries: u32) -> Self {
+        GitHubClient {
+            http: Arc::new(client.clone()),
+            client,
+            credentials: Credentials::App {
+                app,
+                current: Mutex::new(None),
+            },
+            max_retries,
+            rate_limit: RateLimitPolicy::default(),
+            last_remaining: AtomicU32::new(u32::MAX),
+            progress: None,
+            api_url: default_api_url(),
+        }
+    }
+
+    /// The token to send with the next request and its place in the rotation, minting a new installation token if needed.
+    async fn token(&self) -> Result<(Option<Secret>, Option<usize>)> {
+        match &self.credentials {
+            Credentials::Anonymous => Ok((None, None)),
+            Credentials::Tokens(pool) => {
+                let index = pool.select();
+                Ok((Some(pool.tokens[index].token.clone()), Some(index)))
+            }
+            Credentials::App { app, current } => {
+                let mut current = current.lock().await;
+                match current.as_ref() {
+                    Some(installation_token) if !installation_token.needs_refresh() => {
+                        Ok((Some(installation_token.token.clone()), None))
+                    }
+                    _ => {
+                        let installation_token = app.installation_token(&self.client, &self.api_url).await?;
+                        let token = installation_token.token.clone();
+                        *current = Some(installation_token);
+                        Ok((Some(token), None))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send an authenticated GET request to `url` with `query` appended, retrying transient failures.
+    pub async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<Response> {
+        self.send(|| self.client.get(url).query(query)).await
+    }
+
+    /// Send an authenticated POST request with `body` as JSON to `url`, retrying transient failures.
+    pub async fn post_json(&self, url: &str, body: &Value) -> Result<Response> {
+        self.send(|| self.client.post(url).json(body)).await
+    }
+
+    /// Send an authenticated PATCH request without a body to `url`, retrying transient failures.
+    pub async fn patch(&self, url: &str) -> Result<Response> {
+        self.send(|| self.client.patch(url)).await
+    }
+
+    fn warn(&self, message: String) {
+        match &self.progress {
+            Some(progress) => progress.println(message).unwrap_or_default(),
+            None => eprintln!("{}", message),
+        }
+    }
+
+    /// Send the request made by `build`, authenticated and retried.
+    /// Once the rate limit is exhausted, switches to another token if one has requests left, or else
+    /// waits for the limit to reset unless the rate limit policy says to fail.
+    async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response> {
+        loop {
+            let remaining = self.last_remaining.load(Ordering::Relaxed);
+            if remaining < self.rate_limit.warn_below {
+                self.warn(format!("Warning: only {} API requests left before the rate limit resets", remaining));
+            }
+
+            let (token, index) = self.token().await?;
+            let authorization = token.as_ref().map(authorization_header).transpose()?;
+            let response = retry_with_backoff(self.max_retries, || {
+                let request = with_api_headers(build(), authorization.as_ref()).build();
+                async move { self.http.send(request?).await }
+            })
+            .await?;
+            if let Some(remaining) = rate_limit_remaining(&response) {
+                self.last_remaining.store(remaining, Ordering::Relaxed);
+            }
+            if let (Credentials::Tokens(pool), Some(index)) = (&self.credentials, index) {
+                pool.record(index, &response);
+                if rate_limit_reset(&response).is_some() && pool.select() != index {
+                    continue;
+                }
+            }
+
+            match rate_limit_reset(&response) {
+                Some(reset) if self.rate_limit.wait => {
+                    let wait = (reset - Utc::now()).to_std().unwrap_or_default() + Duration::from_secs(1);
+                    self.warn(format!("Rate limit exhausted; waiting {}s until reset", wait.as_secs()));
+                    tokio::time::sleep_until(tokio::time::Instant::now() + wait).await;
+                    self.last_remaining.store(u32::MAX, Ordering::Relaxed);
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+}
+
+/// Add the headers every API request carries, authenticating with `token` if given.
+/// This is the only place a token's value is put on the wire.
+pub fn api_request(request: RequestBuilder, token: Option<&Secret>) -> Result<RequestBuilder> {
+    let authorization = token.map(authorization_header).transpose()?;
+    Ok(with_api_headers(request, authorization.as_ref()))
+}
+
+fn with_api_headers(request: RequestBuilder, authorization: Option<&HeaderValue>) -> RequestBuilder {
+    let request = request
+        .header(USER_AGENT, "pr-comment-analyzer")
+        .header("X-GitHub-Api-Version", API_VERSION);
+    match authorization {
+        Some(value) => request.header(AUTHORIZATION, value.clone()),
+        None => request,
+    }
+}
+
+/// The `Authorization` header for `token`.
+fn authorization_header(token: &Secret) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(&authorization(token.expose()))
+        .map_err(|_| anyhow::anyhow!("The token {} contains characters that can't be sent in a header", token))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// The `Authorization` header value for `token`: classic PATs and OAuth tokens use the `token` scheme,
+/// everything else, like fine-grained PATs (`github_pat_`), installation tokens (`ghs_`) and app JWTs, uses `Bearer`.
+fn authorization(token: &str) -> String {
+    let classic = CLASSIC_TOKEN_PREFIXES.iter().any(|prefix| token.starts_with(prefix)) || is_legacy_token(token);
+    if classic {
+        format!("token {}", token)
+    } else {
+        format!("Bearer {}", token)
+    }
+}
+
+/// Tokens created before prefixes were introduced are 40 hex characters.
+fn is_legacy_token(token: &str) -> bool {
+    token.len() == 40 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Run `request` up to `max_retries` additional times while it fails with a connection error or a retryable status.
+/// Waits `INITIAL_BACKOFF`, doubling each attempt, or as long as `Retry-After` asks for on 429 responses
+/// and on 403 responses of secondary rate limits.
+/// The last response is returned as is once retries are exhausted, so callers still see the final status.
+pub async fn retry_with_backoff<F, Fut>(max_retries: u32, mut request: F) -> reqwest::Result<Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<Response>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+
+    loop {
+        let result = request().await;
+        if attempt >= max_retries {
+            return result;
+        }
+
+        let delay = match &result {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                retry_after(response).unwrap_or(backoff)
+            }
+            // Secondary rate limits, which the search API hits easily, reject with 403 and say when to retry
+            Ok(response) if response.status() == StatusCode::FORBIDDEN => match retry_after(response) {
+                Some(delay) => delay,
+                None => return result,
+            },
+            Ok(response) if RETRYABLE_STATUSES.contains(&response.status()) => backoff,
+            Ok(_) => return result,
+            Err(e) if e.is_connect() || e.is_timeout() => backoff,
+            Err(_) => return result,
+        };
+
+        attempt += 1;
+        match &result {
+            Ok(response) => tracing::debug!(
+                "{} returned {}, retrying in {:?} (attempt {}/{})",
+                response.url(),
+                response.status(),
+                delay,
+                attempt,
+                max_retries
+            ),
+            Err(e) => tracing::debug!(
+                "Request failed: {}, retrying in {:?} (attempt {}/{})",
+                secret::scrub(&e.to_string()),
+                delay,
+                attempt,
+                max_retries
+            ),
+        }
+        tokio::time::sleep(delay).await;
+        backoff *= 2;
+    }
+}
+
+fn rate_limit_remaining(response: &Response) -> Option<u32> {
+    response
+        .headers()
+        .get("x-ratelimit-remaining")?
+        .to_str()
+        .ok()?
+        .parse::<u32>()
+        .ok()
+}
+
+/// When the primary rate limit resets, if `response` was rejected because it is exhausted.
+fn rate_limit_reset(response: &Response) -> Option<DateTime<Utc>> {
+    let status = response.status();
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    if rate_limit_remaining(response)? != 0 {
+        return None;
+    }
+    let reset = response
+        .headers()
+        .get("x-ratelimit-reset")?
+        .to_str()
+        .ok()?
+        .parse::<i64>()
+        .ok()?;
+    DateTime::from_timestamp(reset, 0)
+}
+
+/// The delay requested by a `Retry-After` header given in seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `Authorization` header `api_request` adds for `token`.
+    fn authorization_of(token: &str) -> Result<String> {
+        let request = api_request(Client::new().get("https://api.github.com/user"), Some(&Secret::new(token)))?.build()?;
+        let value = &request.headers()[AUTHORIZATION];
+        assert!(value.is_sensitive());
+        Ok(value.to_str()?.to_string())
+    }
+
+    #[test]
+    fn classic_tokens_use_the_token_scheme_and_others_bearer() -> Result<()> {
+        let legacy = "0123456789abcdef0123456789abcdef01234567";
+        for token in ["ghp_classic", "gho_oauth", legacy] {
+            assert_eq!(authorization_of(token)?, format!("token {}", token));
+        }
+        for token in ["github_pat_11ABC_fine", "ghs_installation", "ghu_user_to_server", "eyJhbGciOiJSUzI1NiJ9.e30.sig", &legacy[1..]] {
+            assert_eq!(authorization_of(token)?, format!("Bearer {}", token));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_unfit_for_a_header_are_an_error() {
+        let error = authorization_of("ghp_line\nbreak-and-more").unwrap_err();
+        assert!(!error.to_string().contains("break-and-more"), "{}", error);
+        assert!(error.to_string().contains("can't be sent in a header"), "{}", error);
+    }
+
+    #[test]
+    fn requests_without_a_token_are_unauthenticated() -> Result<()> {
+        let request = api_request(Client::new().get("https://api.github.com/user"), None)?.build()?;
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+        assert_eq!(request.headers()["X-GitHub-Api-Version"], API_VERSION);
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! Authenticating as a GitHub App installation instead of with a personal token.
+
+use crate::github::api_request;
+use crate::secret::Secret;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Installation tokens are renewed once they are this close to expiring, so requests never race the expiry.
+const REFRESH_MARGIN_MINUTES: i64 = 5;
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A short-lived token acting as an app installation.
+pub struct InstallationToken {
+    pub token: Secret,
+    expires_at: DateTime<Utc>,
+}
+
+impl InstallationToken {
+    /// Whether the token should be replaced before its next use.
+    pub fn needs_refresh(&self) -> bool {
+        Utc::now() + Duration::minutes(REFRESH_MARGIN_MINUTES) >= self.expires_at
+    }
+}
+
+/// The identity of a GitHub App along with the installation to act as.
+pub struct GitHubApp {
+    app_id: u64,
+    key: EncodingKey,
+    pub installation_id: Option<u64>,
+}
+
+impl GitHubApp {
+    /// Load the app's PEM-encoded RSA private key from `private_key_file`.
+    pub fn new(app_id: u64, private_key_file: &Path, installation_id: Option<u64>) -> Result<Self> {
+        let pem = fs::read(private_key_file)
+            .with_context(|| format!("Failed to read private key file {}", private_key_file.display()))?;
+        let key = EncodingKey::from_rsa_pem(&pem)
+            .with_context(|| format!("{} is not a PEM-encoded RSA private key", private_key_file.display()))?;
+
+        Ok(GitHubApp {
+            app_id,
+            key,
+            installation_id,
+        })
+    }
+
+    /// Create a JWT identifying the app, valid for the maximum of ten minutes allowing for clock drift.
+    fn jwt(&self) -> Result<String> {
+        let now = Utc::now();
+        let claims = Claims {
+            iat: (now - Duration::seconds(60)).timestamp(),
+            exp: (now + Duration::minutes(9)).timestamp(),
+            iss: self.app_id.to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key).context("Failed to sign the GitHub App JWT")
+    }
+
+    /// Look up the installation of this app on `owner/repo` with the REST API at `api_url` unless one was configured,
+    /// remembering it for later.
+    pub async fn discover_installation(&mut self, client: &Client, api_url: &str, owner: &str, repo: &str) -> Result<u64> {
+        if let Some(installation_id) = self.installation_id {
+            return Ok(installation_id);
+        }
+
+        let jwt = Secret::new(self.jwt()?);
+        let response = api_request(
+            client.get(format!("{}/repos/{}/{}/installation", api_url, owner, repo)),
+            Some(&jwt),
+        )?
+        .send()
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "GitHub App {} is not installed on {}/{} ({}). Install it or pass --installation-id.",
+                self.app_id,
+                owner,
+                repo,
+                response.status()
+            ));
+        }
+
+        let installation: Installation = response.json().await?;
+        self.installation_id = Some(installation.id);
+        Ok(installation.id)
+    }
+
+    /// Exchange a fresh JWT for an installation access token, asking the REST API at `api_url`.
+    pub async fn installation_token(&self, client: &Client, api_url: &str) -> Result<InstallationToken> {
+        let installation_id = self
+            .installation_id
+            .context("The GitHub App installation must be known before requesting a token")?;
+
+        let jwt = Secret::new(self.jwt()?);
+        let response = api_request(
+            client.post(format!("{}/app/installations/{}/access_tokens", api_url, installation_id)),
+            Some(&jwt),
+        )?
+        .send()
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to create an access token for installation {}: {}",
+                installation_id,
+                response.status()
+            ));
+        }
+
+        let access_token: AccessToken = response.json().await?;
+        Ok(InstallationToken {
+            token: Secret::new(access_token.token),
+            expires_at: access_token.expires_at,
+        })
+    }
+}
@@ -0,0 +1,346 @@
+//! Merge requests on gitlab.com or a self-hosted GitLab, through the REST API authenticated with a `PRIVATE-TOKEN`.
+
+use crate::forge::{Forge, PrCommentData};
+use crate::github::retry_with_backoff;
+use crate::secret::Secret;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use indicatif::ProgressBar;
+use reqwest::header::{HeaderValue, USER_AGENT};
+use reqwest::{Client, Response, StatusCode};
+use serde_json::{json, Value};
+
+/// The GitLab instance used unless `--gitlab-url` names another.
+pub const DEFAULT_URL: &str = "https://gitlab.com";
+
+/// The host of `url`, without credentials or port.
+pub fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    host.split_once(':').map_or(host, |(host, _port)| host)
+}
+
+/// Whether `value` is a URL on the GitLab instance at `url`, whatever its scheme, credentials or port.
+pub fn is_on_instance(value: &str, url: &str) -> bool {
+    value.contains("://") && host(value).eq_ignore_ascii_case(host(url))
+}
+
+/// Split a project URL below `url`, or a `group/subgroup/project` path, into its namespace and name.
+/// Anything after `/-/`, like `/-/merge_requests`, and a trailing `.git` are ignored.
+pub fn parse_project(value: &str, url: &str) -> Result<(String, String)> {
+    let path = match value.split_once("://") {
+        Some(_) if !is_on_instance(value, url) => {
+            return Err(anyhow::anyhow!("{} is not on the GitLab instance {}", value, url));
+        }
+        Some((_, rest)) => rest.split_once('/').map_or("", |(_, path)| path),
+        None => value,
+    };
+    let path = path.split("/-/").next().unwrap_or(path).trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    match path.rsplit_once('/') {
+        Some((namespace, name)) if !namespace.is_empty() && !name.is_empty() => {
+            Ok((namespace.to_string(), name.to_string()))
+        }
+        _ => Err(anyhow::anyhow!("Invalid GitLab project {}. Expected: group/project or {}/group/project", value, url)),
+    }
+}
+
+/// Parse `https://gitlab.com/group/project/-/merge_requests/NUMBER`, ignoring anything after the number like `/diffs`.
+pub fn parse_merge_request_url(value: &str, url: &str) -> Result<(String, String, u32)> {
+    let unsupported = || {
+        anyhow::anyhow!(
+            "Unsupported merge request URL {}. Expected: {}/group/project/-/merge_requests/NUMBER",
+            value,
+            url
+        )
+    };
+    let value = value.split(['?', '#']).next().unwrap_or(value);
+    let (project, rest) = value.split_once("/-/merge_requests/").ok_or_else(unsupported)?;
+    let number = rest.split('/').next().unwrap_or(rest);
+    let (namespace, name) = parse_project(project, url)?;
+    let number = number.parse().with_context(|| format!("Invalid merge request number in {}", value))?;
+    Ok((namespace, name, number))
+}
+
+/// A GitLab instance, accessed with a personal access token or anonymously.
+pub struct GitLabClient {
+    client: Client,
+    url: String,
+    token: Option<Secret>,
+    max_retries: u32,
+}
+
+impl GitLabClient {
+    /// Talk to the GitLab instance at the web address `url`, whose API lives below `/api/v4`.
+    pub fn new(client: Client, url: &str, token: Option<Secret>, max_retries: u32) -> Self {
+        GitLabClient {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            token,
+            max_retries,
+        }
+    }
+
+    /// Fail unless the project `namespace/name` exists and can be read.
+    pub async fn verify_project(&self, namespace: &str, name: &str) -> Result<()> {
+        let response = self.get(&format!("/projects/{}", project_id(namespace, name)), &[]).await?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND => Err(anyhow::anyhow!(
+                "GitLab project {}/{} was not found. It may be private{}.",
+                namespace,
+                name,
+                if self.token.is_some() {
+                    " and not accessible with this token"
+                } else {
+                    ", which needs a token given with --token or GITLAB_TOKEN"
+                }
+            )),
+            _ => Err(self.request_failed(&response)),
+        }
+    }
+
+    /// The discussions of merge request `iid` of `namespace/name`, each with its notes, oldest first. Notes that
+    /// aren't replies are discussions of their own.
+    pub async fn get_mr_discussions(&self, namespace: &str, name: &str, iid: u32, bar: &ProgressBar) -> Result<Vec<Value>> {
+        let path = format!("/projects/{}/merge_requests/{}/discussions", project_id(namespace, name), iid);
+        self.get_all(&path, bar).await
+    }
+
+    /// The notes of merge request `iid` of `namespace/name` in the order of their discussions, without those GitLab
+    /// adds itself, like "added 2 commits". A reply has the id of the note starting its discussion as its
+    /// `in_reply_to_id`, like a reply among GitHub's review comments.
+    pub async fn get_mr_notes(&self, namespace: &str, name: &str, iid: u32, bar: &ProgressBar) -> Result<Vec<Value>> {
+        let discussions = self.get_mr_discussions(namespace, name, iid, bar).await?;
+        Ok(discussions.iter().flat_map(discussion_notes).collect())
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Response> {
+        let url = format!("{}/api/v4{}", self.url, path);
+        tracing::debug!("GET {}", url);
+        let private_token = self.token.as_ref().map(private_token_header).transpose()?;
+        let response = retry_with_backoff(self.max_retries, || {
+            let request = self.client.get(&url).query(query).header(USER_AGENT, "pr-comment-analyzer");
+            match &private_token {
+                Some(value) => request.header("PRIVATE-TOKEN", value.clone()),
+                None => request,
+            }
+            .send()
+        })
+        .await?;
+        Ok(response)
+    }
+
+    /// All items of the list at `path`, following the `x-next-page` header from page to page.
+    async fn get_all(&self, path: &str, bar: &ProgressBar) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut page = "1".to_string();
+        loop {
+            bar.set_message(format!("fetching page {}", page));
+            let response = self.get(path, &[("per_page", "100"), ("page", &page)]).await?;
+            if !response.status().is_success() {
+                return Err(self.request_failed(&response));
+            }
+            let next_page = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string);
+
+            let page_items: Vec<Value> = response.json().await?;
+            items.extend(page_items);
+            match next_page {
+                Some(next_page) => page = next_page,
+                None => return Ok(items),
+            }
+        }
+    }
+
+    fn request_failed(&self, response: &Response) -> anyhow::Error {
+        match response.status() {
+            StatusCode::UNAUTHORIZED => anyhow::anyhow!("GitLab rejected the token ({}) for {}", response.status(), response.url()),
+            StatusCode::TOO_MANY_REQUESTS => anyhow::anyhow!("GitLab rate limit exceeded ({}) for {}", response.status(), response.url()),
+            StatusCode::NOT_FOUND => anyhow::anyhow!(
+                "Not found ({}): {}. The project may be private or the merge request may not exist.",
+                response.status(),
+                response.url()
+            ),
+            status => anyhow::anyhow!("GitLab API request failed: {}", status),
+        }
+    }
+}
+
+impl Forge for GitLabClient {
+    async fn authenticated_user(&self) -> Result<String> {
+        let response = self.get("/user", &[]).await?;
+        if !response.status().is_success() {
+            return Err(self.request_failed(&response));
+        }
+        let user: Value = response.json().await?;
+        user["username"]
+            .as_str()
+            .map(str::to_string)
+            .context("GitLab user without a username in API response")
+    }
+
+    /// Notes on the diff count as review comments, all others as conversation comments. GitLab has no
+    /// reviews with a body of their own.
+    async fn pr_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u32,
+        _since: Option<DateTime<Utc>>,
+        _until: Option<DateTime<Utc>>,
+        bar: &ProgressBar,
+    ) -> Result<PrCommentData> {
+        let mut data = PrCommentData::default();
+        for note in self.get_mr_notes(owner, repo, number, bar).await? {
+            let comment = as_github_comment(&note);
+            if note["type"].as_str() == Some("DiffNote") {
+                data.pr_comments.push(comment);
+            } else {
+                data.issue_comments.push(comment);
+            }
+        }
+        Ok(data)
+    }
+
+    fn pr_url(&self, repository: &str, number: u32) -> String {
+        format!("{}/{}/-/merge_requests/{}", self.url, repository, number)
+    }
+}
+
+/// The `PRIVATE-TOKEN` header for `token`, marked sensitive so it is left out of debug output.
+fn private_token_header(token: &Secret) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(token.expose())
+        .map_err(|_| anyhow::anyhow!("The token {} contains characters that can't be sent in a header", token))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// The project's path with its slashes encoded, as the API takes it in place of the numeric id.
+fn project_id(namespace: &str, name: &str) -> String {
+    format!("{}/{}", namespace, name).replace('/', "%2F")
+}
+
+/// The notes of `discussion` that people wrote, the replies among them with `in_reply_to_id`.
+fn discussion_notes(discussion: &Value) -> Vec<Value> {
+    let notes = discussion["notes"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let first_id = notes.first().and_then(|note| note["id"].as_u64());
+    notes
+        .iter()
+        .enumerate()
+        .filter(|(_, note)| note["system"].as_bool() != Some(true))
+        .map(|(index, note)| {
+            let mut note = note.clone();
+            if let Some(first_id) = first_id.filter(|_| index > 0) {
+                note["in_reply_to_id"] = json!(first_id);
+            }
+            note
+        })
+        .collect()
+}
+
+/// Convert a note to the shape of a GitHub REST comment: `id`, `user.login`, `user.type`, `created_at`, `body`, and
+/// the `path` of notes on the diff and `in_reply_to_id` of replies.
+fn as_github_comment(note: &Value) -> Value {
+    let author = &note["author"];
+    let user_type = if author["bot"].as_bool() == Some(true) { "Bot" } else { "User" };
+    let mut comment = json!({
+        "id": note["id"],
+        "user": { "login": author["username"], "type": user_type },
+        "created_at": note["created_at"],
+        "body": note["body"],
+    });
+    if let Some(path) = note["position"]["new_path"].as_str() {
+        comment["path"] = json!(path);
+    }
+    if let Some(parent) = note["in_reply_to_id"].as_u64() {
+        comment["in_reply_to_id"] = json!(parent);
+    }
+    comment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_unfit_for_a_header_are_an_error() -> Result<()> {
+        let value = private_token_header(&Secret::new("glpat-abc"))?;
+        assert!(value.is_sensitive());
+        assert_eq!(value.to_str()?, "glpat-abc");
+
+        let error = private_token_header(&Secret::new("glpat-line\nbreak-and-more")).unwrap_err();
+        assert!(!error.to_string().contains("break-and-more"), "{}", error);
+        assert!(error.to_string().contains("can't be sent in a header"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn notes_are_shaped_like_github_comments() {
+        let diff_note = json!({
+            "id": 3,
+            "type": "DiffNote",
+            "author": { "username": "alice", "bot": false },
+            "created_at": "2024-01-01T00:00:00Z",
+            "body": "Why not a map?",
+            "position": { "old_path": "src/old.rs", "new_path": "src/new.rs" },
+        });
+        assert_eq!(
+            as_github_comment(&diff_note),
+            json!({
+                "id": 3,
+                "user": { "login": "alice", "type": "User" },
+                "created_at": "2024-01-01T00:00:00Z",
+                "body": "Why not a map?",
+                "path": "src/new.rs",
+            })
+        );
+
+        let note = json!({ "author": { "username": "renovate", "bot": true }, "created_at": "2024-01-02T00:00:00Z", "body": "Rebased" });
+        let comment = as_github_comment(&note);
+        assert_eq!(comment["user"], json!({ "login": "renovate", "type": "Bot" }));
+        assert!(comment.get("path").is_none());
+    }
+
+    #[test]
+    fn replies_point_to_the_note_starting_their_discussion() {
+        let note = |id: u64, system: bool| json!({ "id": id, "system": system, "author": { "username": "alice" } });
+        let discussion = json!({ "notes": [note(1, false), note(2, true), note(3, false), note(4, false)] });
+        let comments: Vec<Value> = discussion_notes(&discussion).iter().map(as_github_comment).collect();
+        let ids: Vec<u64> = comments.iter().filter_map(|comment| comment["id"].as_u64()).collect();
+        assert_eq!(ids, [1, 3, 4], "notes GitLab adds itself are left out");
+        assert!(comments[0].get("in_reply_to_id").is_none());
+        assert_eq!(comments[1]["in_reply_to_id"], 1);
+        assert_eq!(comments[2]["in_reply_to_id"], 1);
+
+        assert!(discussion_notes(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn merge_request_urls_are_parsed() -> Result<()> {
+        for (value, expected) in [
+            ("https://gitlab.com/group/project/-/merge_requests/12", ("group", "project", 12)),
+            ("https://gitlab.com/group/sub/project/-/merge_requests/12/diffs", ("group/sub", "project", 12)),
+            ("https://gitlab.com/group/project/-/merge_requests/12?tab=notes#note_1", ("group", "project", 12)),
+            ("http://GitLab.com:443/group/project/-/merge_requests/7", ("group", "project", 7)),
+        ] {
+            let (namespace, name, number) = parse_merge_request_url(value, DEFAULT_URL)?;
+            assert_eq!((namespace.as_str(), name.as_str(), number), expected, "{}", value);
+        }
+        for value in [
+            "https://gitlab.example.com/group/project/-/merge_requests/12",
+            "https://gitlab.com/group/project/-/merge_requests/",
+            "https://gitlab.com/group/project/-/merge_requests/twelve",
+            "https://gitlab.com/group/project/-/issues/12",
+            "https://gitlab.com/project/-/merge_requests/12",
+        ] {
+            assert!(parse_merge_request_url(value, DEFAULT_URL).is_err(), "{}", value);
+        }
+        Ok(())
+    }
+}
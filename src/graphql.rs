@@ -0,0 +1,449 @@
+//! Fetching all comments of a PR or discussion through the GraphQL API, in one round trip for most PRs, and the
+//! PRs on a Projects board.
+
+use crate::forge::PrCommentData;
+use crate::github::GitHubClient;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// Issue comments and reviews with their comments, each connection paged independently.
+const PR_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!,
+      $commentsCursor: String, $reviewsCursor: String,
+      $withComments: Boolean!, $withReviews: Boolean!) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      comments(first: 100, after: $commentsCursor) @include(if: $withComments) {
+        pageInfo { hasNextPage endCursor }
+        nodes { databaseId author { login __typename ... on User { databaseId } } createdAt body }
+      }
+      reviews(first: 50, after: $reviewsCursor) @include(if: $withReviews) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          id
+          author { login __typename ... on User { databaseId } }
+          submittedAt
+          state
+          body
+          comments(first: 100) {
+            pageInfo { hasNextPage endCursor }
+            nodes { databaseId replyTo { databaseId } author { login __typename ... on User { databaseId } } createdAt path position body }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Further comments of a review with more than fit on the first page.
+const REVIEW_COMMENTS_QUERY: &str = r#"
+query($id: ID!, $cursor: String) {
+  node(id: $id) {
+    ... on PullRequestReview {
+      comments(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes { databaseId replyTo { databaseId } author { login __typename ... on User { databaseId } } createdAt path position body }
+      }
+    }
+  }
+}
+"#;
+
+/// The review threads of a PR, whether they are resolved and the ids of their comments.
+const REVIEW_THREADS_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      reviewThreads(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          id
+          isResolved
+          comments(first: 100) {
+            pageInfo { hasNextPage endCursor }
+            nodes { databaseId }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Further comments of a review thread with more than fit on the first page.
+const REVIEW_THREAD_COMMENTS_QUERY: &str = r#"
+query($id: ID!, $cursor: String) {
+  node(id: $id) {
+    ... on PullRequestReviewThread {
+      comments(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes { databaseId }
+      }
+    }
+  }
+}
+"#;
+
+/// Top-level comments of a discussion with their first replies.
+const DISCUSSION_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    discussion(number: $number) {
+      comments(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          id
+          author { login __typename ... on User { databaseId } }
+          createdAt
+          replies(first: 100) {
+            pageInfo { hasNextPage endCursor }
+            nodes { author { login __typename ... on User { databaseId } } createdAt }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Further replies to a discussion comment with more than fit on the first page.
+const DISCUSSION_REPLIES_QUERY: &str = r#"
+query($id: ID!, $cursor: String) {
+  node(id: $id) {
+    ... on DiscussionComment {
+      replies(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes { author { login __typename ... on User { databaseId } } createdAt }
+      }
+    }
+  }
+}
+"#;
+
+/// The projects of an organization or user whose title contains `title`.
+const PROJECTS_QUERY: &str = r#"
+query($owner: String!, $title: String!) {
+  repositoryOwner(login: $owner) {
+    ... on ProjectV2Owner {
+      projectsV2(first: 100, query: $title) {
+        nodes { id title }
+      }
+    }
+  }
+}
+"#;
+
+/// The items of a project with their status, and the columns the status can take.
+const PROJECT_ITEMS_QUERY: &str = r#"
+query($id: ID!, $cursor: String) {
+  node(id: $id) {
+    ... on ProjectV2 {
+      field(name: "Status") {
+        ... on ProjectV2SingleSelectField { options { name } }
+      }
+      items(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          fieldValueByName(name: "Status") {
+            ... on ProjectV2ItemFieldSingleSelectValue { name }
+          }
+          content {
+            __typename
+            ... on PullRequest { number repository { name owner { login } } }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// A PR on a project board, as its repository's owner and name and its number.
+pub struct ProjectPr {
+    pub owner: String,
+    pub repo: String,
+    pub number: u32,
+}
+
+/// The PRs in the `column` of the Status field of the project of `owner` titled `title`, in board order.
+/// Both are matched ignoring case. Draft issues and issues are skipped, with a count of the drafts for the caller.
+pub async fn get_project_column_prs(
+    github: &GitHubClient,
+    owner: &str,
+    title: &str,
+    column: &str,
+) -> Result<(Vec<ProjectPr>, usize)> {
+    let response = query(github, PROJECTS_QUERY, json!({ "owner": owner, "title": title })).await?;
+    if response["repositoryOwner"].is_null() {
+        return Err(anyhow::anyhow!("There is no organization or user {}", owner));
+    }
+    let projects = &response["repositoryOwner"]["projectsV2"];
+    // The query matches words anywhere in the title, so the exact title is picked from what it finds
+    let id = nodes(projects)
+        .find(|project| project["title"].as_str().is_some_and(|candidate| candidate.eq_ignore_ascii_case(title)))
+        .and_then(|project| project["id"].as_str())
+        .with_context(|| format!("{} has no project titled {}", owner, title))?
+        .to_string();
+
+    let mut prs = Vec::new();
+    let mut drafts = 0;
+    let mut cursor: Option<String> = None;
+    loop {
+        let response = query(github, PROJECT_ITEMS_QUERY, json!({ "id": id, "cursor": cursor })).await?;
+        let project = &response["node"];
+        let columns: Vec<&str> = project["field"]["options"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|option| option["name"].as_str())
+            .collect();
+        if columns.is_empty() {
+            return Err(anyhow::anyhow!("Project {} of {} has no Status field to pick a column from", title, owner));
+        }
+        if !columns.iter().any(|name| name.eq_ignore_ascii_case(column)) {
+            return Err(anyhow::anyhow!(
+                "Project {} of {} has no column {}. Its columns are: {}",
+                title,
+                owner,
+                column,
+                columns.join(", ")
+            ));
+        }
+
+        let items = &project["items"];
+        for item in nodes(items) {
+            let status = item["fieldValueByName"]["name"].as_str();
+            if !status.is_some_and(|status| status.eq_ignore_ascii_case(column)) {
+                continue;
+            }
+            let content = &item["content"];
+            match content["__typename"].as_str() {
+                Some("PullRequest") => {}
+                Some("DraftIssue") => {
+                    drafts += 1;
+                    continue;
+                }
+                _ => continue,
+            }
+            let repository = &content["repository"];
+            let (Some(owner), Some(repo), Some(number)) = (
+                repository["owner"]["login"].as_str(),
+                repository["name"].as_str(),
+                content["number"].as_u64().and_then(|number| u32::try_from(number).ok()),
+            ) else {
+                continue;
+            };
+            prs.push(ProjectPr { owner: owner.to_string(), repo: repo.to_string(), number });
+        }
+
+        let (has_next_page, next_cursor) = next_page(items);
+        if !has_next_page {
+            return Ok((prs, drafts));
+        }
+        cursor = next_cursor;
+    }
+}
+
+pub async fn fetch_pr_data_graphql(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u32,
+) -> Result<PrCommentData> {
+    let mut data = PrCommentData::default();
+    let mut comments_cursor: Option<String> = None;
+    let mut reviews_cursor: Option<String> = None;
+    let mut with_comments = true;
+    let mut with_reviews = true;
+
+    while with_comments || with_reviews {
+        let variables = json!({
+            "owner": owner,
+            "repo": repo,
+            "number": pr_number,
+            "commentsCursor": comments_cursor,
+            "reviewsCursor": reviews_cursor,
+            "withComments": with_comments,
+            "withReviews": with_reviews,
+        });
+        let response = query(github, PR_QUERY, variables).await?;
+        let pull_request = &response["repository"]["pullRequest"];
+        if pull_request.is_null() {
+            return Err(anyhow::anyhow!("Pull request {}/{}#{} was not found", owner, repo, pr_number));
+        }
+
+        if with_comments {
+            let comments = &pull_request["comments"];
+            data.issue_comments.extend(nodes(comments).map(|node| as_rest_comment(node, "createdAt", "created_at")));
+            (with_comments, comments_cursor) = next_page(comments);
+        }
+
+        if with_reviews {
+            let reviews = &pull_request["reviews"];
+            for review in nodes(reviews) {
+                data.review_comments.push(as_rest_comment(review, "submittedAt", "submitted_at"));
+
+                let comments = &review["comments"];
+                data.pr_comments.extend(nodes(comments).map(|node| as_rest_comment(node, "createdAt", "created_at")));
+                if let (true, cursor) = next_page(comments) {
+                    let id = review["id"].as_str().context("Review without an id in GraphQL response")?;
+                    data.pr_comments
+                        .extend(remaining_nested(github, REVIEW_COMMENTS_QUERY, "comments", id, cursor).await?);
+                }
+            }
+            (with_reviews, reviews_cursor) = next_page(reviews);
+        }
+    }
+
+    Ok(data)
+}
+
+/// The ids of the review comments in the resolved threads of a PR, which match the `id` of REST review comments.
+pub async fn get_resolved_comment_ids(github: &GitHubClient, owner: &str, repo: &str, pr_number: u32) -> Result<HashSet<u64>> {
+    let mut ids = HashSet::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let variables = json!({ "owner": owner, "repo": repo, "number": pr_number, "cursor": cursor });
+        let response = query(github, REVIEW_THREADS_QUERY, variables).await?;
+        let pull_request = &response["repository"]["pullRequest"];
+        if pull_request.is_null() {
+            return Err(anyhow::anyhow!("Pull request {}/{}#{} was not found", owner, repo, pr_number));
+        }
+
+        let threads = &pull_request["reviewThreads"];
+        for thread in nodes(threads).filter(|thread| thread["isResolved"].as_bool() == Some(true)) {
+            let comments = &thread["comments"];
+            let mut thread_comments: Vec<Value> = nodes(comments).map(|node| as_rest_comment(node, "createdAt", "created_at")).collect();
+            if let (true, comments_cursor) = next_page(comments) {
+                let id = thread["id"].as_str().context("Review thread without an id in GraphQL response")?;
+                thread_comments.extend(remaining_nested(github, REVIEW_THREAD_COMMENTS_QUERY, "comments", id, comments_cursor).await?);
+            }
+            ids.extend(thread_comments.iter().filter_map(|comment| comment["id"].as_u64()));
+        }
+
+        let (has_next_page, next_cursor) = next_page(threads);
+        if !has_next_page {
+            return Ok(ids);
+        }
+        cursor = next_cursor;
+    }
+}
+
+/// All comments and replies of a discussion, shaped like REST comments.
+pub async fn get_discussion_comments_graphql(
+    github: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    discussion_number: u32,
+) -> Result<Vec<Value>> {
+    let mut comments = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let variables = json!({ "owner": owner, "repo": repo, "number": discussion_number, "cursor": cursor });
+        let response = query(github, DISCUSSION_QUERY, variables).await?;
+        let discussion = &response["repository"]["discussion"];
+        if discussion.is_null() {
+            return Err(anyhow::anyhow!("Discussion {}/{}#{} was not found", owner, repo, discussion_number));
+        }
+
+        let connection = &discussion["comments"];
+        for comment in nodes(connection) {
+            comments.push(as_rest_comment(comment, "createdAt", "created_at"));
+
+            let replies = &comment["replies"];
+            comments.extend(nodes(replies).map(|node| as_rest_comment(node, "createdAt", "created_at")));
+            if let (true, reply_cursor) = next_page(replies) {
+                let id = comment["id"].as_str().context("Discussion comment without an id in GraphQL response")?;
+                comments.extend(remaining_nested(github, DISCUSSION_REPLIES_QUERY, "replies", id, reply_cursor).await?);
+            }
+        }
+
+        let (has_next_page, next_cursor) = next_page(connection);
+        if !has_next_page {
+            return Ok(comments);
+        }
+        cursor = next_cursor;
+    }
+}
+
+/// Page through the `field` connection of node `id` with `query`, starting after `cursor`.
+async fn remaining_nested(
+    github: &GitHubClient,
+    query_text: &str,
+    field: &str,
+    id: &str,
+    mut cursor: Option<String>,
+) -> Result<Vec<Value>> {
+    let mut comments = Vec::new();
+    loop {
+        let response = query(github, query_text, json!({ "id": id, "cursor": cursor })).await?;
+        let connection = &response["node"][field];
+        comments.extend(nodes(connection).map(|node| as_rest_comment(node, "createdAt", "created_at")));
+
+        let (has_next_page, next_cursor) = next_page(connection);
+        if !has_next_page {
+            return Ok(comments);
+        }
+        cursor = next_cursor;
+    }
+}
+
+/// Run a GraphQL query, returning its `data` or the errors GitHub reported.
+async fn query(github: &GitHubClient, query: &str, variables: Value) -> Result<Value> {
+    let response = github
+        .post_json(&github.graphql_url(), &json!({ "query": query, "variables": variables }))
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("GraphQL request failed: {}", response.status()));
+    }
+
+    let mut body: Value = response.json().await?;
+    if let Some(errors) = body["errors"].as_array().filter(|errors| !errors.is_empty()) {
+        let messages: Vec<&str> = errors.iter().filter_map(|error| error["message"].as_str()).collect();
+        return Err(anyhow::anyhow!("GraphQL query failed: {}", messages.join("; ")));
+    }
+    Ok(body["data"].take())
+}
+
+fn nodes(connection: &Value) -> impl Iterator<Item = &Value> {
+    connection["nodes"].as_array().into_iter().flatten()
+}
+
+/// Whether `connection` has another page, and the cursor to fetch it with.
+fn next_page(connection: &Value) -> (bool, Option<String>) {
+    let page_info = &connection["pageInfo"];
+    (
+        page_info["hasNextPage"].as_bool().unwrap_or(false),
+        page_info["endCursor"].as_str().map(str::to_string),
+    )
+}
+
+/// Convert a GraphQL node to the REST shape: `user.login`, `user.id`, `user.type`, a snake_case timestamp, the
+/// `body`, the `id` of comments, the `path`, `position` and `in_reply_to_id` of review comments and the `state` of
+/// reviews.
+fn as_rest_comment(node: &Value, timestamp_field: &str, rest_timestamp_field: &str) -> Value {
+    let author = &node["author"];
+    let user = if author.is_null() {
+        // Deleted accounts ("ghost") have no author
+        Value::Null
+    } else {
+        json!({ "login": author["login"], "id": author["databaseId"], "type": author["__typename"] })
+    };
+    let mut comment = json!({ "user": user, rest_timestamp_field: node[timestamp_field] });
+    for field in ["body", "path", "position", "state"] {
+        if let Some(value) = node.get(field) {
+            comment[field] = value.clone();
+        }
+    }
+    if let Some(id) = node.get("databaseId") {
+        comment["id"] = id.clone();
+    }
+    if let Some(parent) = node["replyTo"].get("databaseId") {
+        comment["in_reply_to_id"] = parent.clone();
+    }
+    comment
+}
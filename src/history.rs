@@ -0,0 +1,180 @@
+//! Keeping the results of each analysis in a SQLite database, to follow the time per comment over the weeks.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+/// Created on first use; `run_at` is an RFC 3339 timestamp in UTC, so that it sorts as text.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS analyses (
+    id INTEGER PRIMARY KEY,
+    run_at TEXT NOT NULL,
+    user_login TEXT NOT NULL,
+    repository TEXT NOT NULL,
+    total_minutes REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS pr_results (
+    analysis_id INTEGER NOT NULL REFERENCES analyses (id),
+    pr_number INTEGER NOT NULL,
+    pr_comments INTEGER NOT NULL,
+    review_comments INTEGER NOT NULL,
+    issue_comments INTEGER NOT NULL
+);
+";
+
+/// The counts of one analyzed PR.
+pub struct PrCounts {
+    pub pr_number: u32,
+    pub pr_comments: u32,
+    pub review_comments: u32,
+    pub issue_comments: u32,
+}
+
+/// The PRs of one repository in an analysis, with the share of the minutes allocated to them.
+pub struct RepositoryAnalysis<'a> {
+    pub repository: &'a str,
+    pub total_minutes: f64,
+    pub prs: Vec<PrCounts>,
+}
+
+/// The analyses recorded in a week, starting on Monday in the local timezone.
+pub struct WeeklyAverage {
+    pub week: NaiveDate,
+    pub analyses: u32,
+    pub comments: u64,
+    /// `None` without comments.
+    pub minutes_per_comment: Option<f64>,
+}
+
+fn open(path: &Path) -> Result<Connection> {
+    let connection = Connection::open(path).with_context(|| format!("Failed to open the database {}", path.display()))?;
+    connection
+        .execute_batch(SCHEMA)
+        .with_context(|| format!("Failed to create the tables of {}", path.display()))?;
+    Ok(connection)
+}
+
+/// Append an analysis of `user_login` run at `run_at` to the database at `path`, as a row per repository.
+pub fn record(path: &Path, run_at: DateTime<Utc>, user_login: &str, repositories: &[RepositoryAnalysis]) -> Result<()> {
+    let mut connection = open(path)?;
+    let transaction = connection.transaction()?;
+    let run_at = run_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+    for analysis in repositories {
+        transaction.execute(
+            "INSERT INTO analyses (run_at, user_login, repository, total_minutes) VALUES (?1, ?2, ?3, ?4)",
+            params![run_at, user_login, analysis.repository, analysis.total_minutes],
+        )?;
+        let analysis_id = transaction.last_insert_rowid();
+        for pr in &analysis.prs {
+            transaction.execute(
+                "INSERT INTO pr_results (analysis_id, pr_number, pr_comments, review_comments, issue_comments) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![analysis_id, pr.pr_number, pr.pr_comments, pr.review_comments, pr.issue_comments],
+            )?;
+        }
+    }
+    transaction
+        .commit()
+        .with_context(|| format!("Failed to record the analysis in {}", path.display()))
+}
+
+/// The minutes per comment of each of the last `weeks` weeks up to `now`, most recent first, as all minutes of the
+/// week over all its comments, of `user_login` only if given.
+pub fn weekly_averages(path: &Path, weeks: u32, user_login: Option<&str>, now: DateTime<Local>) -> Result<Vec<WeeklyAverage>> {
+    let connection = open(path)?;
+    let today = now.date_naive();
+    let this_week = today - chrono::Duration::days(today.weekday().num_days_from_monday().into());
+    let mut averages: Vec<WeeklyAverage> = (0..weeks)
+        .map(|week| WeeklyAverage {
+            week: this_week - chrono::Duration::weeks(week.into()),
+            analyses: 0,
+            comments: 0,
+            minutes_per_comment: None,
+        })
+        .collect();
+    let first_week = averages.last().map_or(this_week, |average| average.week);
+    let since = first_week
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .context("The first week starts at a time that doesn't exist locally")?
+        .with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let mut statement = connection.prepare(
+        "SELECT analyses.run_at, analyses.total_minutes,
+                COALESCE(SUM(pr_results.pr_comments + pr_results.review_comments + pr_results.issue_comments), 0)
+         FROM analyses LEFT JOIN pr_results ON pr_results.analysis_id = analyses.id
+         WHERE analyses.run_at >= ?1 AND (?2 IS NULL OR analyses.user_login = ?2)
+         GROUP BY analyses.id",
+    )?;
+    let rows = statement.query_map(params![since, user_login], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, u64>(2)?))
+    })?;
+    let mut minutes = vec![0.0; averages.len()];
+    for row in rows {
+        let (run_at, total_minutes, comments) = row?;
+        let run_at: DateTime<Utc> = run_at.parse().with_context(|| format!("Invalid run_at {} in {}", run_at, path.display()))?;
+        let day = run_at.with_timezone(&Local).date_naive();
+        let Some(index) = averages.iter().position(|average| average.week <= day) else {
+            continue;
+        };
+        averages[index].analyses += 1;
+        averages[index].comments += comments;
+        minutes[index] += total_minutes;
+    }
+    for (average, minutes) in averages.iter_mut().zip(minutes) {
+        average.minutes_per_comment = (average.comments > 0).then(|| minutes / average.comments as f64);
+    }
+    Ok(averages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(year: i32, month: u32, day: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    fn record_at(path: &Path, day: DateTime<Local>, user_login: &str, total_minutes: f64, comments: &[u32]) {
+        let prs = comments
+            .iter()
+            .enumerate()
+            .map(|(index, &comments)| PrCounts {
+                pr_number: index as u32 + 1,
+                pr_comments: comments,
+                review_comments: 0,
+                issue_comments: 0,
+            })
+            .collect();
+        let analysis = RepositoryAnalysis { repository: "o/r", total_minutes, prs };
+        record(path, day.with_timezone(&Utc), user_login, &[analysis]).unwrap();
+    }
+
+    #[test]
+    fn analyses_are_averaged_per_week_and_user() {
+        let path = std::env::temp_dir().join(format!("pr-comment-analyzer-{}-history.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        record_at(&path, local(2024, 1, 16), "me", 60.0, &[4, 2]);
+        record_at(&path, local(2024, 1, 8), "me", 30.0, &[]);
+        record_at(&path, local(2024, 1, 14), "other", 20.0, &[2]);
+        record_at(&path, local(2023, 12, 31), "me", 100.0, &[1]);
+
+        let averages = weekly_averages(&path, 3, None, local(2024, 1, 17)).unwrap();
+        let weeks: Vec<_> = averages.iter().map(|average| average.week.to_string()).collect();
+        assert_eq!(weeks, ["2024-01-15", "2024-01-08", "2024-01-01"], "weeks start on Monday, most recent first");
+        let counts: Vec<_> = averages.iter().map(|average| (average.analyses, average.comments, average.minutes_per_comment)).collect();
+        assert_eq!(
+            counts,
+            [(1, 6, Some(10.0)), (2, 2, Some(25.0)), (0, 0, None)],
+            "an analysis without PRs adds its minutes but no comments, and those before the first week are left out"
+        );
+
+        let averages = weekly_averages(&path, 2, Some("me"), local(2024, 1, 17)).unwrap();
+        let counts: Vec<_> = averages.iter().map(|average| (average.analyses, average.comments, average.minutes_per_comment)).collect();
+        assert_eq!(counts, [(1, 6, Some(10.0)), (1, 0, None)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Caching GET responses by their `ETag`, so that repeated requests, as with `--watch`, are answered with
+//! 304 Not Modified, which costs no rate limit.
+
+use crate::github::HttpClient;
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK};
+use reqwest::{Client, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Sends requests with `client`, remembering the last response to each URL in memory, and in `directory` if given.
+pub struct CachedClient {
+    client: Client,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+    directory: Option<PathBuf>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    /// The URL, as different URLs may share a file name.
+    url: String,
+    etag: String,
+    /// The `Link` header, which pagination follows and a 304 response doesn't repeat.
+    link: Option<String>,
+    body: String,
+}
+
+impl CachedClient {
+    /// Cache in memory, and in the files of `directory` across invocations, creating it if needed.
+    pub fn new(client: Client, directory: Option<PathBuf>) -> Result<Self> {
+        if let Some(directory) = &directory {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("Failed to create cache directory {}", directory.display()))?;
+        }
+        Ok(CachedClient {
+            client,
+            entries: Mutex::new(HashMap::new()),
+            directory,
+        })
+    }
+
+    fn lookup(&self, url: &str) -> Option<CachedResponse> {
+        if let Some(cached) = self.entries.lock().expect("cache lock is never poisoned").get(url) {
+            return Some(cached.clone());
+        }
+        let cached: CachedResponse = serde_json::from_slice(&fs::read(self.path(url)?).ok()?).ok()?;
+        (cached.url == url).then_some(cached)
+    }
+
+    /// Remember `cached`; failing to write it to disk only costs a full response next time.
+    fn store(&self, cached: CachedResponse) {
+        if let Some(path) = self.path(&cached.url) {
+            let written = serde_json::to_vec(&cached).map_err(anyhow::Error::from).and_then(|json| Ok(fs::write(&path, json)?));
+            if let Err(e) = written {
+                tracing::debug!("Failed to write cache file {}: {}", path.display(), e);
+            }
+        }
+        self.entries.lock().expect("cache lock is never poisoned").insert(cached.url.clone(), cached);
+    }
+
+    fn path(&self, url: &str) -> Option<PathBuf> {
+        self.directory.as_ref().map(|directory| directory.join(format!("{:016x}.json", url_hash(url))))
+    }
+}
+
+impl HttpClient for CachedClient {
+    fn send(&self, mut request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+        Box::pin(async move {
+            if request.method() != Method::GET {
+                return self.client.execute(request).await;
+            }
+            let url = request.url().to_string();
+            let cached = self.lookup(&url);
+            if let Some(etag) = cached.as_ref().and_then(|cached| HeaderValue::from_str(&cached.etag).ok()) {
+                request.headers_mut().insert(IF_NONE_MATCH, etag);
+            }
+
+            let response = self.client.execute(request).await?;
+            if let (Some(cached), StatusCode::NOT_MODIFIED) = (cached, response.status()) {
+                tracing::debug!("{} is not modified, using the cached response", url);
+                // The 304 response has the current rate limit headers, the cache has the rest
+                let mut headers = response.headers().clone();
+                headers.remove(CONTENT_LENGTH);
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                if let Some(link) = cached.link.as_deref().and_then(|link| HeaderValue::from_str(link).ok()) {
+                    headers.insert(LINK, link);
+                }
+                return Ok(build_response(StatusCode::OK, headers, cached.body.into_bytes()));
+            }
+
+            let etag = response.headers().get(ETAG).and_then(|etag| etag.to_str().ok()).map(str::to_string);
+            let Some(etag) = etag.filter(|_| response.status().is_success()) else {
+                return Ok(response);
+            };
+            // The body is read to keep a copy, so the response is rebuilt around it
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+            if let Ok(text) = std::str::from_utf8(&body) {
+                self.store(CachedResponse {
+                    url,
+                    etag,
+                    link: headers.get(LINK).and_then(|link| link.to_str().ok()).map(str::to_string),
+                    body: text.to_string(),
+                });
+            }
+            Ok(build_response(status, headers, body.to_vec()))
+        })
+    }
+}
+
+fn build_response(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Response::from(response)
+}
+
+/// The 64-bit FNV-1a hash of `url`, which names its cache file and, unlike `DefaultHasher`, is stable across builds.
+fn url_hash(url: &str) -> u64 {
+    url.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
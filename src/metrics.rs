@@ -0,0 +1,125 @@
+//! Serving the results and the number of API requests as Prometheus metrics for `--metrics-port`.
+
+use crate::github::HttpClient;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request as HttpRequest, Response as HttpResponse, Server, StatusCode};
+use reqwest::{Request, Response};
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The comments of a user in a repository, as of the last complete analysis.
+#[derive(Debug)]
+pub struct Gauge {
+    pub repository: String,
+    pub user: String,
+    pub comments: u32,
+    /// `None` if the time per comment isn't known, as without comments.
+    pub minutes_per_comment: Option<f64>,
+}
+
+/// The metrics served, updated as the analysis goes.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    gauges: Mutex<Vec<Gauge>>,
+    api_requests: AtomicU64,
+}
+
+impl Metrics {
+    /// Replace the gauges by those of the analysis that just completed.
+    pub fn set_gauges(&self, gauges: Vec<Gauge>) {
+        *self.gauges.lock().expect("metrics lock is never poisoned") = gauges;
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    fn render(&self) -> String {
+        let gauges = self.gauges.lock().expect("metrics lock is never poisoned");
+        let mut text = String::new();
+        text.push_str("# HELP pr_analyzer_total_comments The comments counted in the analyzed PRs.\n");
+        text.push_str("# TYPE pr_analyzer_total_comments gauge\n");
+        for gauge in gauges.iter() {
+            let _ = writeln!(text, "pr_analyzer_total_comments{} {}", labels(gauge), gauge.comments);
+        }
+        text.push_str("# HELP pr_analyzer_minutes_per_comment The time spent per counted comment, in minutes.\n");
+        text.push_str("# TYPE pr_analyzer_minutes_per_comment gauge\n");
+        for gauge in gauges.iter() {
+            if let Some(minutes_per_comment) = gauge.minutes_per_comment {
+                let _ = writeln!(text, "pr_analyzer_minutes_per_comment{} {}", labels(gauge), minutes_per_comment);
+            }
+        }
+        text.push_str("# HELP pr_analyzer_api_requests_total The API requests sent.\n");
+        text.push_str("# TYPE pr_analyzer_api_requests_total counter\n");
+        let _ = writeln!(text, "pr_analyzer_api_requests_total {}", self.api_requests.load(Ordering::Relaxed));
+        text
+    }
+}
+
+fn labels(gauge: &Gauge) -> String {
+    format!("{{repository=\"{}\",user=\"{}\"}}", escape(&gauge.repository), escape(&gauge.user))
+}
+
+/// Escape a label value as the exposition format requires.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Sends requests with `http`, counting them in `metrics`.
+pub struct CountingClient {
+    http: Arc<dyn HttpClient>,
+    metrics: Arc<Metrics>,
+}
+
+impl CountingClient {
+    pub fn new(http: Arc<dyn HttpClient>, metrics: Arc<Metrics>) -> Self {
+        CountingClient { http, metrics }
+    }
+}
+
+impl HttpClient for CountingClient {
+    fn send(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+        self.metrics.api_requests.fetch_add(1, Ordering::Relaxed);
+        self.http.send(request)
+    }
+}
+
+/// Serve `metrics` at `/metrics` on all interfaces on `port` in the background, for as long as the tool runs.
+/// Fails right away if the port can't be bound.
+pub fn serve(port: u16, metrics: Arc<Metrics>) -> Result<()> {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    // The cause, like the port being in use, is the part worth showing
+    let server = Server::try_bind(&address)
+        .map_err(|e| anyhow::anyhow!("Failed to serve metrics on port {}: {}", port, std::error::Error::source(&e).unwrap_or(&e)))?;
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = respond(&metrics, &request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    tokio::spawn(async move {
+        if let Err(e) = server.serve(make_service).await {
+            eprintln!("Warning: the metrics server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+fn respond(metrics: &Metrics, request: &HttpRequest<Body>) -> HttpResponse<Body> {
+    let mut response = HttpResponse::new(Body::empty());
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    }
+    *response.body_mut() = Body::from(metrics.render());
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
@@ -0,0 +1,140 @@
+//! Expanding ranges of PR numbers like `1500-1542` given on the command line.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+/// Ranges of more PRs than this need to be confirmed, as each PR costs several API requests.
+pub const CONFIRMATION_THRESHOLD: u32 = 200;
+
+/// Replace each range `A-B` or `owner/repo#A-B` in `items` by the PRs from A to B inclusive, keeping all other
+/// items as they are. Ranges of more than `CONFIRMATION_THRESHOLD` PRs are only expanded if `confirm` agrees
+/// when given the range and its size.
+pub fn expand(items: &[&str], mut confirm: impl FnMut(&str, u32) -> Result<bool>) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for &item in items {
+        let Some((prefix, first, last)) = parse_range(item)? else {
+            expanded.push(item.to_string());
+            continue;
+        };
+        let count = last - first + 1;
+        if count > CONFIRMATION_THRESHOLD && !confirm(item, count)? {
+            return Err(anyhow::anyhow!("Not analyzing the {} PRs of {}", count, item));
+        }
+        expanded.extend((first..=last).map(|number| format!("{}{}", prefix, number)));
+    }
+    Ok(expanded)
+}
+
+/// The numbers in `inputs`, each a number or a range `N-M`, in the order first given and without duplicates.
+/// Unlike `expand`, ranges of any size are expanded without asking.
+pub fn expand_pr_ranges(inputs: &[String]) -> Result<Vec<u32>> {
+    let items: Vec<&str> = inputs.iter().map(String::as_str).collect();
+    let mut seen = HashSet::new();
+    let mut numbers = Vec::new();
+    for item in expand(&items, |_, _| Ok(true))? {
+        let number = item
+            .trim()
+            .parse::<u32>()
+            .with_context(|| format!("Invalid number {}. Expected a number or a range like 10-20", item))?;
+        if seen.insert(number) {
+            numbers.push(number);
+        }
+    }
+    Ok(numbers)
+}
+
+/// Split a range into what comes before its numbers, like `owner/repo#`, and its bounds.
+/// Returns `None` for anything but a range, such as single numbers and URLs.
+fn parse_range(item: &str) -> Result<Option<(&str, u32, u32)>> {
+    if item.contains("://") {
+        return Ok(None);
+    }
+    let (prefix, numbers) = match item.rsplit_once('#') {
+        Some((repository, numbers)) => (&item[..repository.len() + 1], numbers),
+        None => ("", item),
+    };
+    let Some((first, last)) = numbers.split_once('-') else {
+        return Ok(None);
+    };
+
+    let parse = |bound: &str| {
+        bound
+            .trim()
+            .parse::<u32>()
+            .with_context(|| format!("Invalid PR range {}. Expected: FIRST-LAST, like 1500-1542", item))
+    };
+    let (first, last) = (parse(first)?, parse(last)?);
+    // Also keeps the size of the range within a `u32`
+    if first == 0 {
+        return Err(anyhow::anyhow!("Invalid PR range {}: PR numbers start at 1", item));
+    }
+    if first > last {
+        return Err(anyhow::anyhow!("Invalid PR range {}: {} is greater than {}", item, first, last));
+    }
+    Ok(Some((prefix, first, last)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn never_confirm(range: &str, count: u32) -> Result<bool> {
+        panic!("{} of {} PRs needs no confirmation", range, count)
+    }
+
+    #[test]
+    fn ranges_and_single_numbers_mix() -> Result<()> {
+        let expanded = expand(&["7", "3-5", "o/r#10-11", "https://github.com/o/r/pull/12", "o/r#13"], never_confirm)?;
+        assert_eq!(expanded, ["7", "3", "4", "5", "o/r#10", "o/r#11", "https://github.com/o/r/pull/12", "o/r#13"]);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_range_tells_ranges_from_other_items() -> Result<()> {
+        assert_eq!(parse_range("1500-1542")?, Some(("", 1500, 1542)));
+        assert_eq!(parse_range("owner/repo#2-2")?, Some(("owner/repo#", 2, 2)));
+        assert_eq!(parse_range("42")?, None);
+        assert_eq!(parse_range("owner/repo#42")?, None);
+        assert_eq!(parse_range("https://github.com/owner/my-repo/pull/42")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        for range in ["5-3", "0-4294967295", "1-x", "-5", "1-4294967296"] {
+            assert!(parse_range(range).is_err(), "{} should be rejected", range);
+            assert!(expand(&[range], |_, _| Ok(true)).is_err(), "{} should be rejected", range);
+        }
+    }
+
+    #[test]
+    fn large_ranges_need_confirmation() -> Result<()> {
+        let at_threshold = format!("1-{}", CONFIRMATION_THRESHOLD);
+        assert_eq!(expand(&[&at_threshold], never_confirm)?.len(), CONFIRMATION_THRESHOLD as usize);
+
+        let beyond = format!("o/r#1-{}", CONFIRMATION_THRESHOLD + 1);
+        let mut asked = Vec::new();
+        let result = expand(&[&beyond], |range, count| {
+            asked.push((range.to_string(), count));
+            Ok(false)
+        });
+        assert!(result.is_err());
+        assert_eq!(asked, [(beyond.clone(), CONFIRMATION_THRESHOLD + 1)]);
+
+        let expanded = expand(&[&beyond], |_, _| Ok(true))?;
+        assert_eq!(expanded.len(), CONFIRMATION_THRESHOLD as usize + 1);
+        assert_eq!(expanded.last().map(String::as_str), Some(format!("o/r#{}", CONFIRMATION_THRESHOLD + 1).as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn expand_pr_ranges_mixes_numbers_and_ranges_without_duplicates() -> Result<()> {
+        let inputs = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+        assert_eq!(expand_pr_ranges(&inputs(&["12", "10-13", "7", "11"]))?, [12, 10, 11, 13, 7]);
+        assert_eq!(expand_pr_ranges(&inputs(&[]))?, Vec::<u32>::new());
+        for invalid in ["13-10", "x", "1-x", "4294967296", "o/r#5"] {
+            assert!(expand_pr_ranges(&inputs(&["1", invalid])).is_err(), "{} should be rejected", invalid);
+        }
+        Ok(())
+    }
+}
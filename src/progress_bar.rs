@@ -0,0 +1,58 @@
+//! Progress bars drawn on stderr while PRs are analyzed.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// A bar counting analyzed PRs, below one line per PR in flight.
+pub struct PrProgress {
+    bars: MultiProgress,
+    overall: ProgressBar,
+}
+
+impl PrProgress {
+    /// Track the analysis of `pr_count` PRs, drawing only if `visible`.
+    pub fn new(pr_count: usize, visible: bool) -> Self {
+        let target = if visible {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        let bars = MultiProgress::with_draw_target(target);
+        let overall = bars.add(ProgressBar::new(pr_count as u64).with_style(
+            ProgressStyle::with_template("[{bar:30}] {pos}/{len} PRs analyzed")
+                .expect("progress template is valid")
+                .progress_chars("=> "),
+        ));
+        PrProgress { bars, overall }
+    }
+
+    /// The bars, for printing messages above them without garbling the output.
+    pub fn bars(&self) -> MultiProgress {
+        self.bars.clone()
+    }
+
+    /// Add a line for PR `pr_number` whose message the fetchers update.
+    pub fn start_pr(&self, pr_number: u32) -> ProgressBar {
+        let bar = self.bars.insert_before(
+            &self.overall,
+            ProgressBar::new_spinner()
+                .with_style(ProgressStyle::with_template("{prefix}: {msg}").expect("progress template is valid"))
+                .with_prefix(format!("PR #{}", pr_number)),
+        );
+        bar.set_message("starting");
+        bar
+    }
+
+    /// Remove the line of a finished PR and count it.
+    pub fn finish_pr(&self, bar: ProgressBar) {
+        bar.finish_and_clear();
+        self.bars.remove(&bar);
+        self.overall.inc(1);
+    }
+}
+
+impl Drop for PrProgress {
+    /// Remove all bars, also of PRs abandoned on an error, so the report or error is printed cleanly.
+    fn drop(&mut self) {
+        self.bars.clear().ok();
+    }
+}
@@ -0,0 +1,63 @@
+//! Remembering which git remote to analyze in working trees whose remotes point to different repositories.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where the choices are kept: `$XDG_CACHE_HOME/pr-comment-analyzer/remote-choices.toml`, or under `~/.cache`.
+fn choices_path() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("pr-comment-analyzer").join("remote-choices.toml"))
+}
+
+/// The chosen remote of each working tree, keyed by its top-level directory.
+fn load_choices(path: &Path) -> Result<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| anyhow::anyhow!("Invalid remote choices file {}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read remote choices file {}", path.display())),
+    }
+}
+
+fn store_choices(path: &Path, choices: &BTreeMap<String, String>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let contents = toml::to_string(choices).context("Failed to serialize remote choices")?;
+    fs::write(path, contents).with_context(|| format!("Failed to write remote choices file {}", path.display()))
+}
+
+/// The remote chosen earlier for the working tree at `worktree`, if any.
+pub fn get(worktree: &Path) -> Result<Option<String>> {
+    let Some(path) = choices_path() else {
+        return Ok(None);
+    };
+    Ok(load_choices(&path)?.remove(&worktree.to_string_lossy().into_owned()))
+}
+
+/// Remember `remote` as the one to use in the working tree at `worktree`.
+pub fn set(worktree: &Path, remote: &str) -> Result<()> {
+    let path = choices_path().context("Neither XDG_CACHE_HOME nor HOME is set, so the choice can't be remembered")?;
+    let mut choices = load_choices(&path)?;
+    choices.insert(worktree.to_string_lossy().into_owned(), remote.to_string());
+    store_choices(&path, &choices)
+}
+
+/// Forget the remote chosen for the working tree at `worktree`, returning `false` if there was none.
+pub fn forget(worktree: &Path) -> Result<bool> {
+    let Some(path) = choices_path() else {
+        return Ok(false);
+    };
+    let mut choices = load_choices(&path)?;
+    if choices.remove(&worktree.to_string_lossy().into_owned()).is_none() {
+        return Ok(false);
+    }
+    store_choices(&path, &choices)?;
+    Ok(true)
+}
@@ -0,0 +1,95 @@
+//! Keeping tokens out of errors, logs and debug output.
+
+use std::fmt;
+use std::sync::Mutex;
+
+/// Every secret created so far, so messages can be scrubbed of them before they are printed.
+static KNOWN_SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A token whose value is only available through `expose`, and which formats as its mask.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        let value = value.into();
+        if !value.is_empty() {
+            if let Ok(mut known) = KNOWN_SECRETS.lock() {
+                if !known.contains(&value) {
+                    known.push(value.clone());
+                }
+            }
+        }
+        Secret(value)
+    }
+
+    /// The actual token, for building requests.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// The token with all but its last four characters hidden.
+    pub fn masked(&self) -> String {
+        mask(&self.0)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", self.masked())
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.masked())
+    }
+}
+
+/// Hide all but the last four characters of `value`, like `****abcd`.
+pub fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+/// Replace every known secret in `text` with its mask.
+pub fn scrub(text: &str) -> String {
+    let mut text = text.to_string();
+    if let Ok(known) = KNOWN_SECRETS.lock() {
+        // Longer secrets first, so one containing another isn't left partly readable
+        let mut known: Vec<&String> = known.iter().collect();
+        known.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        for secret in known {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), &mask(secret));
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn tokens_never_appear_in_scrubbed_errors() {
+        let token = Secret::new("ghp_scrubTestToken1234567890");
+        let longer = Secret::new("ghp_scrubTestToken1234567890-and-more");
+        let error = Err::<(), _>(anyhow::anyhow!("401 for Authorization: token {}", token.expose()))
+            .with_context(|| format!("Failed to validate {} and {}", longer.expose(), token.expose()))
+            .unwrap_err();
+        for text in [format!("Error: {}", error), format!("{:#}", error), format!("{:?}", error)] {
+            let scrubbed = scrub(&text);
+            assert!(!scrubbed.contains(token.expose()), "{}", scrubbed);
+            assert!(!scrubbed.contains("and-more"), "{}", scrubbed);
+            assert!(scrubbed.contains(&token.masked()), "{}", scrubbed);
+        }
+        assert_eq!(format!("{} {:?}", token, token), "****7890 Secret(****7890)");
+    }
+}
@@ -0,0 +1,118 @@
+//! A stand-in for the GitHub API, serving canned responses on a local port and recording the requests.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+
+pub struct MockApi {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockApi {
+    /// Serve in the background for as long as the test runs.
+    pub fn start() -> MockApi {
+        MockApi::serving(Vec::new())
+    }
+
+    /// Serve like [`MockApi::start()`], but answer the paths in `responses` with their values.
+    pub fn serving(responses: Vec<(&'static str, Value)>) -> MockApi {
+        let responses = Arc::new(responses);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (recorded, responses) = (recorded.clone(), responses.clone());
+                std::thread::spawn(move || serve(stream, &recorded, &responses));
+            }
+        });
+        MockApi { url, requests }
+    }
+
+    /// The paths requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Run the tool with `args` against this API, as in a GitHub Actions workflow with a token and no config file.
+    pub fn run(&self, args: &[&str], home: &Path) -> Output {
+        self.command(args, home).output().unwrap()
+    }
+
+    /// The command [`MockApi::run()`] runs, to be adjusted before running it.
+    pub fn command(&self, args: &[&str], home: &Path) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pr-comment-analyzer"));
+        command
+            .args(args)
+            .env_clear()
+            .env("HOME", home)
+            .env("XDG_CONFIG_HOME", home)
+            .env("XDG_CACHE_HOME", home)
+            .env("GITHUB_ACTIONS", "true")
+            .env("GITHUB_API_URL", &self.url)
+            .env("GITHUB_GRAPHQL_URL", format!("{}/graphql", self.url))
+            .env("GITHUB_TOKEN", "test-token")
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default());
+        command
+    }
+}
+
+/// Answer the requests on `stream`, which are all GETs, until the client closes it.
+fn serve(stream: TcpStream, recorded: &Mutex<Vec<String>>, responses: &[(&str, Value)]) {
+    let mut reader = BufReader::new(&stream);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let path = target.split_once('?').map_or(target, |(path, _query)| path);
+        recorded.lock().unwrap().push(path.to_string());
+
+        let body = match responses.iter().find(|(response_path, _)| *response_path == path) {
+            Some((_, response)) => response.to_string(),
+            None => respond(path).to_string(),
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        if (&stream).write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// The authenticated user `me`, the repository `o/r` and PRs of `alice` without any comments.
+fn respond(path: &str) -> Value {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match segments[..] {
+        ["user"] => json!({ "login": "me", "id": 1 }),
+        ["repos", owner, repo] => json!({
+            "full_name": format!("{}/{}", owner, repo),
+            "name": repo,
+            "owner": { "login": owner },
+            "permissions": { "pull": true },
+        }),
+        ["repos", _, _, "pulls", number] => json!({
+            "number": number.parse::<u32>().unwrap_or(0),
+            "user": { "login": "alice" },
+            "state": "open",
+            "created_at": "2024-01-01T00:00:00Z",
+            "base": { "ref": "main" },
+        }),
+        _ => json!([]),
+    }
+}
@@ -0,0 +1,26 @@
+// Only serving and running are used here
+#[allow(dead_code)]
+mod common;
+
+use common::MockApi;
+use serde_json::json;
+
+#[test]
+fn forks_are_pointed_out_without_failing() {
+    let fork = json!({
+        "full_name": "me/r",
+        "private": false,
+        "fork": true,
+        "parent": { "full_name": "o/r" },
+    });
+    let api = MockApi::serving(vec![("/repos/me/r", fork)]);
+    let home = std::env::temp_dir().join(format!("pr-comment-analyzer-doctor-{}", std::process::id()));
+    let output = api.run(&["doctor", "--repository", "me/r"], &home);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("me/r is a fork of o/r"), "{}", stdout);
+
+    let output = api.run(&["doctor", "--repository", "o/r"], &home);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("o/r is not a fork"), "{}", stdout);
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::MockApi;
+
+#[test]
+fn duplicate_prs_are_fetched_once() {
+    let api = MockApi::start();
+    let home = std::env::temp_dir().join(format!("pr-comment-analyzer-test-{}", std::process::id()));
+    let output = api.run(&["-m", "30", "-r", "o/r", "42", "43", "42", "42-43"], &home);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Note: duplicate PR numbers removed: #42, #43"), "{}", stdout);
+    let requests = api.requests();
+    for number in [42, 43] {
+        let comments = format!("/repos/o/r/pulls/{}/comments", number);
+        assert_eq!(requests.iter().filter(|path| **path == comments).count(), 1, "{:?}", requests);
+    }
+}
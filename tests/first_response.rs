@@ -0,0 +1,26 @@
+// Only serving and running are used here
+#[allow(dead_code)]
+mod common;
+
+use common::MockApi;
+use serde_json::{json, Value};
+
+#[test]
+fn comments_before_since_are_still_the_first_response() {
+    let comment = json!({
+        "id": 1,
+        "user": { "login": "me", "id": 1 },
+        "created_at": "2024-01-01T02:30:00Z",
+        "body": "Looks good",
+    });
+    let api = MockApi::serving(vec![("/repos/o/r/issues/42/comments", json!([comment]))]);
+    let home = std::env::temp_dir().join(format!("pr-comment-analyzer-test-{}", std::process::id()));
+    let args = ["-m", "30", "-r", "o/r", "--first-response", "--since", "2024-02-01", "--format", "json", "42"];
+    let output = api.run(&args, &home);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let result = &report["per_pr"][0];
+    assert_eq!(result["total"], 0, "{}", report);
+    assert_eq!(result["first_response_minutes"], 150, "{}", report);
+}
@@ -0,0 +1,70 @@
+// Only the command of the mock API is used here
+#[allow(dead_code)]
+mod common;
+
+use common::MockApi;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// Start a proxy refusing every request, returning its URL and the request lines it receives.
+fn refusing_proxy() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            let _ = BufReader::new(&stream).read_line(&mut line);
+            received.lock().unwrap().push(line.trim_end().to_string());
+            let _ = stream.write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n");
+        }
+    });
+    (url, requests)
+}
+
+/// Run the tool against an API only reachable through a proxy, with `configure` adding the proxy settings.
+/// Returns the request lines the proxy passed to `configure` received.
+fn run_through_proxy(configure: impl Fn(&mut Command, &str)) -> Vec<String> {
+    let api = MockApi::start();
+    let home = std::env::temp_dir().join(format!("pr-comment-analyzer-test-{}-proxy", std::process::id()));
+    let (proxy, requests) = refusing_proxy();
+    let mut command = api.command(&["-m", "30", "--max-retries", "0", "-r", "o/r", "42"], &home);
+    command
+        .env("GITHUB_API_URL", "https://api.proxied.test")
+        .env("GITHUB_GRAPHQL_URL", "https://api.proxied.test/graphql");
+    configure(&mut command, &proxy);
+    let output = command.output().unwrap();
+    assert!(!output.status.success(), "the proxy refuses, and the API host doesn't resolve");
+    let received = requests.lock().unwrap().clone();
+    received
+}
+
+const CONNECT: &str = "CONNECT api.proxied.test:443 HTTP/1.1";
+
+#[test]
+fn https_proxy_is_honored() {
+    for variable in ["HTTPS_PROXY", "https_proxy"] {
+        let requests = run_through_proxy(|command, proxy| {
+            command.env(variable, proxy);
+        });
+        assert_eq!(requests.first().map(String::as_str), Some(CONNECT), "{}", variable);
+    }
+}
+
+#[test]
+fn proxy_flag_overrides_the_environment_and_no_proxy_skips_it() {
+    let (unused, unused_requests) = refusing_proxy();
+    let requests = run_through_proxy(|command, proxy| {
+        command.env("HTTPS_PROXY", &unused).args(["--proxy", proxy]);
+    });
+    assert_eq!(requests.first().map(String::as_str), Some(CONNECT));
+    assert!(unused_requests.lock().unwrap().is_empty());
+
+    let requests = run_through_proxy(|command, proxy| {
+        command.env("HTTPS_PROXY", proxy).env("NO_PROXY", "proxied.test");
+    });
+    assert!(requests.is_empty(), "{:?}", requests);
+}
@@ -0,0 +1,46 @@
+// Only the command of the mock API is used here
+#[allow(dead_code)]
+mod common;
+
+use common::MockApi;
+use std::path::Path;
+use std::process::Command;
+
+/// A git repository in a fresh temporary directory whose `origin` is `remote/repo` on GitHub.
+fn git_repository(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("pr-comment-analyzer-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for args in [&["init", "-q"][..], &["remote", "add", "origin", "https://github.com/remote/repo.git"]] {
+        assert!(Command::new("git").args(args).current_dir(&dir).status().unwrap().success());
+    }
+    dir
+}
+
+/// The repository whose comments on PR 42 were fetched when run with `args` in `dir`, with `GITHUB_REPOSITORY` set if given.
+fn fetched_repository(dir: &Path, args: &[&str], github_repository: Option<&str>) -> String {
+    let api = MockApi::start();
+    let mut command = api.command(&[args, &["-m", "30", "42"]].concat(), dir);
+    command.current_dir(dir);
+    if let Some(repository) = github_repository {
+        command.env("GITHUB_REPOSITORY", repository);
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let requests = api.requests();
+    let pr = requests
+        .iter()
+        .find(|path| path.ends_with("/pulls/42/comments"))
+        .unwrap_or_else(|| panic!("No comments on PR 42 were fetched: {:?}", requests));
+    pr.trim_start_matches("/repos/").trim_end_matches("/pulls/42/comments").to_string()
+}
+
+#[test]
+fn repository_flag_beats_github_repository_beats_git_remote() {
+    let dir = git_repository("detection");
+    assert_eq!(fetched_repository(&dir, &["-r", "flag/repo"], Some("env/repo")), "flag/repo");
+    assert_eq!(fetched_repository(&dir, &[], Some("env/repo")), "env/repo");
+    assert_eq!(fetched_repository(&dir, &[], Some("")), "remote/repo");
+    assert_eq!(fetched_repository(&dir, &[], None), "remote/repo");
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,19 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pr-comment-analyzer"))
+        .args(args)
+        .args(["-m", "30", "-r", "o/r", "1"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn token_sources_exclude_each_other() {
+    for args in [&["--token", "a", "--token-file", "token.txt"][..], &["--app-id", "1", "--token-file", "token.txt"]] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("cannot be used with"), "{}", stderr);
+    }
+}